@@ -78,7 +78,7 @@ export async function establishPayer(): Promise<void> {
     const {feeCalculator} = await connection.getRecentBlockhash();
 
     // Calculate the cost to fund the greeter account
-    fees += await connection.getMinimumBalanceForRentExemption(49);
+    fees += await connection.getMinimumBalanceForRentExemption(310);
 
     // Calculate the cost of sending transactions
     fees += feeCalculator.lamportsPerSignature * 100; // wag
@@ -182,10 +182,26 @@ export async function mintToken(): Promise<void> {
 
   const byteArray = [3];
   const amount = longToByteArray(10000);
-  const list = [Buffer.from(byteArray), Buffer.from(amount)];
+  const sequence = longToByteArray(0);
+  // source Ethereum event: tx hash, log index, block number
+  const ethTxHash = Buffer.alloc(32);
+  const ethLogIndex = longToByteArray(0);
+  const ethBlockNumber = longToByteArray(0);
+  const list = [
+    Buffer.from(byteArray),
+    Buffer.from(amount),
+    Buffer.from(sequence),
+    ethTxHash,
+    Buffer.from(ethLogIndex),
+    Buffer.from(ethBlockNumber),
+  ];
   const buffer = Buffer.concat(list);
 
   const accountPubKey = await PublicKey.findProgramAddress([Buffer.from("Locker"), Buffer.from("Init")], programId);
+  // sequences 0 and 1 both fall in the first bitmap window
+  const processedPubKey = await PublicKey.findProgramAddress([Buffer.from("Locker"), Buffer.from("Processed"), Buffer.from(longToByteArray(0))], programId);
+  // marks the source Ethereum event as consumed
+  const eventPubKey = await PublicKey.findProgramAddress([Buffer.from("Locker"), Buffer.from("Event"), ethTxHash, Buffer.from(ethLogIndex)], programId);
   const tokenAccountPubKey = new PublicKey('2Fjsu78vgi9FsEMjrTTpfHNRvK1Z9hx4Q6D1dQdog5mS');
   const tokenMintPubKey = new PublicKey('CpM3TgsV6WeJLaaLSpk4AdsZD9AUMdvFZiFUNF5hrzp5');
   console.log('token-mint', tokenMintPubKey.toString());
@@ -194,11 +210,14 @@ export async function mintToken(): Promise<void> {
 
   const instruction = new TransactionInstruction({
     keys: [
-      {pubkey: payerAccount.publicKey, isSigner: true, isWritable: false},
+      {pubkey: payerAccount.publicKey, isSigner: true, isWritable: true},
       {pubkey: accountPubKey[0], isSigner: false, isWritable: true},
       {pubkey: tokenAccountPubKey, isSigner: false, isWritable: true},
       {pubkey: tokenMintPubKey, isSigner: false, isWritable: true},
-      {pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false}
+      {pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false},
+      {pubkey: processedPubKey[0], isSigner: false, isWritable: true},
+      {pubkey: SystemProgram.programId, isSigner: false, isWritable: false},
+      {pubkey: eventPubKey[0], isSigner: false, isWritable: true},
     ],
     programId,
     data: buffer,
@@ -280,20 +299,25 @@ export async function lockToken(): Promise<void> {
 export async function releaseToken(): Promise<void> {
   const byteArray = [2];
   const amount = longToByteArray(1000000000);
-  const list = [Buffer.from(byteArray), Buffer.from(amount)];
+  const sequence = longToByteArray(1);
+  const list = [Buffer.from(byteArray), Buffer.from(amount), Buffer.from(sequence)];
   const buffer = Buffer.concat(list);
 
   const destinationAccount = await readAccountFromFile(path.join(path.resolve(__dirname, '../../dist/token-account'), 'token-account-keypair.json'));
   const accountPubKey = await PublicKey.findProgramAddress([Buffer.from("Locker"), Buffer.from("Init")], programId);
-  const mintlogPubKey = await PublicKey.findProgramAddress([Buffer.from("Locker"), Buffer.from("Mint")], programId);
+  const releaseRecordPubKey = await PublicKey.findProgramAddress([Buffer.from("Locker"), Buffer.from("Released"), destinationAccount.publicKey.toBuffer()], programId);
+  // sequences 0 and 1 both fall in the first bitmap window
+  const processedPubKey = await PublicKey.findProgramAddress([Buffer.from("Locker"), Buffer.from("Processed"), Buffer.from(longToByteArray(0))], programId);
   const systemPubKey = SystemProgram.programId;
 
   const instruction = new TransactionInstruction({
     keys: [
-      {pubkey: payerAccount.publicKey, isSigner: true, isWritable: false},
+      {pubkey: payerAccount.publicKey, isSigner: true, isWritable: true},
       {pubkey: accountPubKey[0], isSigner: false, isWritable: true},
       {pubkey: destinationAccount.publicKey, isSigner: false, isWritable: true},
       {pubkey: systemPubKey, isSigner: false, isWritable: false},
+      {pubkey: releaseRecordPubKey[0], isSigner: false, isWritable: true},
+      {pubkey: processedPubKey[0], isSigner: false, isWritable: true},
     ],
     programId,
     data: buffer,
@@ -0,0 +1,72 @@
+use solana_program::keccak;
+use crate::error::LockerError;
+use crate::types::{DESTINATION_CHAIN_ADDRESS_LEN, ETH_ADDRESS_LEN};
+
+/// Places an Ethereum address in the destination buffer, left padded with
+/// zeros if the buffer is wider than an address.
+pub fn eth_address_to_destination(address: [u8; ETH_ADDRESS_LEN]) -> [u8; DESTINATION_CHAIN_ADDRESS_LEN] {
+    let mut destination = [0u8; DESTINATION_CHAIN_ADDRESS_LEN];
+    destination[DESTINATION_CHAIN_ADDRESS_LEN - ETH_ADDRESS_LEN..].copy_from_slice(&address);
+    destination
+}
+
+/// Checks the EIP-55 checksum of a hex encoded address. Addresses written
+/// entirely in lower or upper case carry no checksum and are accepted.
+pub fn validate_eth_checksum(address: &str) -> bool {
+    let hex = address.strip_prefix("0x").unwrap_or(address);
+    if hex.len() != 2 * ETH_ADDRESS_LEN || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return false;
+    }
+    if !hex.chars().any(|c| c.is_ascii_lowercase()) || !hex.chars().any(|c| c.is_ascii_uppercase()) {
+        return true;
+    }
+
+    let hash = keccak::hash(hex.to_ascii_lowercase().as_bytes()).to_bytes();
+    hex.chars().enumerate().all(|(i, c)| {
+        let nibble = if i % 2 == 0 { hash[i / 2] >> 4 } else { hash[i / 2] & 0x0f };
+        if c.is_ascii_digit() {
+            true
+        } else if nibble >= 8 {
+            c.is_ascii_uppercase()
+        } else {
+            c.is_ascii_lowercase()
+        }
+    })
+}
+
+/// Decodes a hex encoded Ethereum address, rejecting it if the checksum does
+/// not match.
+pub fn parse_eth_address(address: &str) -> Result<[u8; ETH_ADDRESS_LEN], LockerError> {
+    if !validate_eth_checksum(address) {
+        return Err(LockerError::InvalidEthAddress);
+    }
+    let hex = address.strip_prefix("0x").unwrap_or(address).as_bytes();
+    let mut bytes = [0u8; ETH_ADDRESS_LEN];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        let pair = std::str::from_utf8(&hex[2 * i..2 * i + 2]).map_err(|_| LockerError::InvalidEthAddress)?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| LockerError::InvalidEthAddress)?;
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_checksummed_address() {
+        let address = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        assert!(validate_eth_checksum(address));
+        let bytes = parse_eth_address(address).unwrap();
+        assert_eq!(bytes[0], 0x5a);
+        assert_eq!(bytes[ETH_ADDRESS_LEN - 1], 0xed);
+        assert_eq!(eth_address_to_destination(bytes)[DESTINATION_CHAIN_ADDRESS_LEN - ETH_ADDRESS_LEN..], bytes);
+    }
+
+    #[test]
+    fn test_parse_corrupted_checksum() {
+        let address = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD";
+        assert!(!validate_eth_checksum(address));
+        assert!(matches!(parse_eth_address(address), Err(LockerError::InvalidEthAddress)));
+    }
+}
@@ -15,6 +15,150 @@ pub enum LockerError {
     /// Unexpected conversion
     #[error("Unexpected Decimal Conversion")]
     UnexpectedDecimalConversion,
+
+    /// Token account holds less than the requested burn amount
+    #[error("Insufficient Token Balance")]
+    InsufficientTokenBalance,
+
+    /// Mint would exceed the per-epoch mint cap
+    #[error("Epoch Cap Exceeded")]
+    EpochCapExceeded,
+
+    /// Arithmetic overflow while updating state
+    #[error("Arithmetic Overflow")]
+    ArithmeticOverflow,
+
+    /// Lock would push total_locked above the deposit cap
+    #[error("Deposit Cap Exceeded")]
+    DepositCapExceeded,
+
+    /// Malformed or badly checksummed Ethereum address
+    #[error("Invalid Eth Address")]
+    InvalidEthAddress,
+
+    /// Instruction entered while another one is still updating state
+    #[error("Reentrancy")]
+    Reentrancy,
+
+    /// Decimals outside the supported range
+    #[error("Invalid Decimals")]
+    InvalidDecimals,
+
+    /// Inbound event was already processed
+    #[error("Already Processed")]
+    AlreadyProcessed,
+
+    /// Account data does not start with the expected magic header
+    #[error("Bad Magic")]
+    BadMagic,
+
+    /// Large release is missing the backup authority's signature
+    #[error("Insufficient Signers")]
+    InsufficientSigners,
+
+    /// Account is not the program address derived from the expected seeds
+    #[error("Invalid PDA")]
+    InvalidPda,
+
+    /// Log account predates the current log layout and must be resized
+    #[error("Log Account Too Small")]
+    LogAccountTooSmall,
+
+    /// Amount converts to zero underlying tokens
+    #[error("Amount Too Small")]
+    AmountTooSmall,
+
+    /// Mint account is uninitialized or controlled by someone else
+    #[error("Invalid Mint")]
+    InvalidMint,
+
+    /// Mint exceeds the per-transaction maximum
+    #[error("Amount Above Maximum")]
+    AmountAboveMaximum,
+
+    /// Minted supply would exceed the locked collateral
+    #[error("Insolvent Mint")]
+    InsolventMint,
+
+    /// Release destination is the vault itself
+    #[error("Invalid Destination")]
+    InvalidDestination,
+
+    /// No config change is queued
+    #[error("No Pending Config Change")]
+    NoPendingConfigChange,
+
+    /// Queued config change is still inside the timelock
+    #[error("Config Change Locked")]
+    ConfigChangeLocked,
+
+    /// Initializer cannot pay rent for the state and log accounts
+    #[error("Insufficient Funds For Init")]
+    InsufficientFundsForInit,
+
+    /// Token account still holds tokens and cannot be closed
+    #[error("Token Account Not Empty")]
+    TokenAccountNotEmpty,
+
+    /// Fill would pay out more than the release obligation
+    #[error("Release Overfilled")]
+    ReleaseOverfilled,
+
+    /// Proof does not lead from the claimed leaf to the release root
+    #[error("Invalid Merkle Proof")]
+    InvalidMerkleProof,
+
+    /// Release record saw a release within the inactivity window
+    #[error("Release Record Active")]
+    ReleaseRecordActive,
+
+    /// Vault holds less than the amount above its rent reserve
+    #[error("Insufficient Liquidity")]
+    InsufficientLiquidity,
+
+    /// Token account is not owned by the signer or does not match the mint
+    #[error("Token Owner Mismatch")]
+    TokenOwnerMismatch,
+
+    /// Token account is frozen by the mint's freeze authority
+    #[error("Token Account Frozen")]
+    TokenAccountFrozen,
+
+    /// Instruction was encoded for a wire version this program does not speak
+    #[error("Unsupported Instruction Version")]
+    UnsupportedInstructionVersion,
+
+    /// Max mint per transaction is above the epoch mint cap
+    #[error("Max Mint Above Epoch Cap")]
+    MaxMintAboveEpochCap,
+
+    /// Large release threshold is above the deposit cap
+    #[error("Threshold Above Deposit Cap")]
+    ThresholdAboveDepositCap,
+
+    /// Metadata account does not match the key or is not owned by the metadata program
+    #[error("Invalid Metadata Account")]
+    InvalidMetadataAccount,
+
+    /// Release exceeds the total locked
+    #[error("Insufficient Locked")]
+    InsufficientLocked,
+
+    /// Burn exceeds the total minted
+    #[error("Insufficient Minted")]
+    InsufficientMinted,
+
+    /// Account is not owned by this program
+    #[error("Invalid Account Owner")]
+    InvalidAccountOwner,
+
+    /// Account the instruction modifies was passed read-only
+    #[error("Account Not Writable")]
+    AccountNotWritable,
+
+    /// Locker state account is already initialized
+    #[error("Already Initialized")]
+    AlreadyInitialized,
 }
 
 impl From<LockerError> for ProgramError {
@@ -1,4 +1,4 @@
-use std::convert::{TryFrom, TryInto};
+use std::convert::TryInto;
 use solana_program::{
     program_error::ProgramError,
     pubkey::Pubkey,
@@ -6,12 +6,21 @@ use solana_program::{
 };
 use arrayref::{array_ref, array_refs};
 use crate::error::LockerError;
-use crate::types::DESTINATION_CHAIN_ADDRESS_LEN;
+use crate::types::{DEFAULT_UNDERLYING_DECIMALS, DESTINATION_CHAIN_ADDRESS_LEN, ETH_ADDRESS_LEN};
+
+/// Wire version carried in the top bits of the tag byte. Bumped whenever an
+/// upgrade changes the layout of an existing instruction.
+pub const INSTRUCTION_VERSION: u8 = 0;
+/// Low bits of the tag byte that select the instruction
+const TAG_BITS: u8 = 6;
 
 #[repr(C)]
 #[derive(Debug, PartialEq)]
 pub struct Initialize {
-    pub authority: Pubkey
+    pub authority: Pubkey,
+    pub backup_authority: Pubkey,
+    pub metadata: Pubkey,
+    pub underlying_decimals: u8
 }
 
 #[repr(C)]
@@ -24,20 +33,165 @@ pub struct LockandMint {
 #[repr(C)]
 #[derive(Debug, PartialEq)]
 pub struct Release {
-    pub amount: u64
+    pub amount: u64,
+    pub sequence: u64
 }
 
 #[repr(C)]
 #[derive(Debug, PartialEq)]
 pub struct Mint {
-    pub amount: u64
+    pub amount: u64,
+    pub sequence: u64,
+    pub eth_tx_hash: [u8; 32],
+    pub eth_log_index: u64,
+    pub eth_block_number: u64,
+    /// Decimals the client expects the locker to store for the mint,
+    /// checked when present
+    pub expected_spl_decimals: Option<u8>
 }
 
 #[repr(C)]
 #[derive(Debug, PartialEq)]
 pub struct BurnAndRelease {
     pub amount: u64,
-    pub destination: [u8; DESTINATION_CHAIN_ADDRESS_LEN]
+    pub destination: [u8; DESTINATION_CHAIN_ADDRESS_LEN],
+    /// Close the emptied token account afterwards, set by tag 24
+    pub close_account: bool,
+    /// Decimals the client expects the locker to store for the mint,
+    /// checked when present.
+    /// Sent as the single byte after the destination; a payload padded
+    /// with more than one byte carries none.
+    pub expected_spl_decimals: Option<u8>
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct SetEpochMintCap {
+    pub cap: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct SetDepositCap {
+    pub cap: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct SetBackupAuthority {
+    pub backup_authority: Pubkey
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct AdminBurn {
+    pub amount: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct SetLargeReleaseThreshold {
+    pub threshold: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct PreviewRelease {
+    pub amount: u64,
+    pub destination: Pubkey
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct ResizeLogAccount {
+    pub direction: LogDirection
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct RecordClaim {
+    pub amount: u64,
+    pub sequence: u64,
+    pub recipient: Pubkey,
+    pub eth_tx_hash: [u8; 32],
+    pub eth_log_index: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct ClaimMint {
+    pub eth_tx_hash: [u8; 32],
+    pub eth_log_index: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct SetMaxMintPerTx {
+    pub max: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct SetEthContract {
+    pub eth_contract: [u8; ETH_ADDRESS_LEN]
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct StartRelease {
+    pub amount: u64,
+    pub sequence: u64,
+    pub destination: Pubkey
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct FillRelease {
+    pub sequence: u64,
+    pub amount: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct SetReleaseRoot {
+    pub root: [u8; 32]
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct ClaimReleaseWithProof {
+    pub amount: u64,
+    pub burn_id: u64,
+    pub proof: Vec<[u8; 32]>
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct SetLocalMode {
+    pub enabled: bool
+}
+
+/// Limits to change together, `None` keeps the current value
+#[repr(C)]
+#[derive(Debug, Default, PartialEq)]
+pub struct UpdateConfig {
+    pub epoch_mint_cap: Option<u64>,
+    pub deposit_cap: Option<u64>,
+    pub large_release_threshold: Option<u64>,
+    pub max_mint_per_tx: Option<u64>
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogDirection {
+    LockAndMint,
+    BurnAndRelease,
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct ReadLog {
+    pub direction: LogDirection
 }
 
 #[repr(C)]
@@ -48,26 +202,72 @@ pub enum LockerInstruction {
     Release(Release),
     Mint(Mint),
     BurnAndRelease(BurnAndRelease),
+    SetEpochMintCap(SetEpochMintCap),
+    ReadLog(ReadLog),
+    SetDepositCap(SetDepositCap),
+    SetBackupAuthority(SetBackupAuthority),
+    ClaimMintAuthority,
+    AdminBurn(AdminBurn),
+    SetLargeReleaseThreshold(SetLargeReleaseThreshold),
+    CreateReleaseRecord,
+    GetInFlight,
+    PreviewRelease(PreviewRelease),
+    ResizeLogAccount(ResizeLogAccount),
+    GetHealth,
+    RecordClaim(RecordClaim),
+    ClaimMint(ClaimMint),
+    SetMaxMintPerTx(SetMaxMintPerTx),
+    GetRoles,
+    ApplyConfigChange,
+    SetEthContract(SetEthContract),
+    GetEthContract,
+    StartRelease(StartRelease),
+    FillRelease(FillRelease),
+    SetReleaseRoot(SetReleaseRoot),
+    ClaimReleaseWithProof(ClaimReleaseWithProof),
+    CloseRecipientAccount,
+    SetLocalMode(SetLocalMode),
+    DumpState,
+    UpdateConfig(UpdateConfig),
+    GetMetadata,
+    MigrateState,
 }
 
 impl LockerInstruction {
+    /// Decodes an instruction. `LockAndMint`, `BurnAndRelease` and the
+    /// instructions without a payload tolerate trailing bytes by default;
+    /// building with `strict-parsing` requires exact lengths.
+    /// `BurnAndRelease` reads the expected decimals only from a payload
+    /// exactly one byte longer than the destination, so older clients
+    /// padding the payload are not mistaken for a decimals check.
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
         let (&tag, rest) = input.split_first().ok_or(LockerError::InvalidInstruction)?;
+        if tag >> TAG_BITS != INSTRUCTION_VERSION {
+            return Err(LockerError::UnsupportedInstructionVersion.into());
+        }
+        let tag = tag & ((1 << TAG_BITS) - 1);
         match tag {
             0 => {
-                if rest.len() >= 32usize {
-                    let authority_bytes = match <[u8; 32]>::try_from(rest) {
-                        Ok(value) => value,
-                        Err(_) => return Err(LockerError::InvalidAuthority.into()),
-                    };
-                    return Ok(Self::Initialize(Initialize{
-                        authority: Pubkey::new_from_array(authority_bytes),
-                    }));
-                }
-                Err(LockerError::InvalidAuthority.into())
+                // the backup authority and metadata account are optional,
+                // leaving either out stores the default key. A byte after
+                // the metadata key sets the underlying decimals.
+                let key = |offset: usize| Pubkey::new_from_array(*array_ref![rest, offset, 32]);
+                let (backup_authority, metadata, underlying_decimals) = match rest.len() {
+                    32 => (Pubkey::default(), Pubkey::default(), DEFAULT_UNDERLYING_DECIMALS),
+                    64 => (key(32), Pubkey::default(), DEFAULT_UNDERLYING_DECIMALS),
+                    96 => (key(32), key(64), DEFAULT_UNDERLYING_DECIMALS),
+                    97 => (key(32), key(64), rest[96]),
+                    _ => return Err(LockerError::InvalidAuthority.into()),
+                };
+                Ok(Self::Initialize(Initialize{
+                    authority: Pubkey::new_from_array(*array_ref![rest, 0, 32]),
+                    backup_authority,
+                    metadata,
+                    underlying_decimals,
+                }))
             }
             1 => {
-                if rest.len() >= 8 + DESTINATION_CHAIN_ADDRESS_LEN {
+                if Self::payload_fits(rest, 8 + DESTINATION_CHAIN_ADDRESS_LEN) {
                     let src = array_ref![rest, 0, 8 + DESTINATION_CHAIN_ADDRESS_LEN];
                     let (
                         amount,
@@ -81,23 +281,33 @@ impl LockerInstruction {
                 Err(LockerError::InvalidInstruction.into())
             }
             2 => {
-                if rest.len() == 8 {
+                if rest.len() == 16 {
                     return Ok(Self::Release(Release{
-                        amount: Self::unpack_amount(rest)?,
+                        amount: Self::unpack_amount(&rest[..8])?,
+                        sequence: Self::unpack_amount(&rest[8..])?,
                     }));
                 }
                 return Err(LockerError::InvalidInstruction.into());
             }
             3 => {
-                if rest.len() == 8 {
+                // an optional trailing byte carries the expected decimals
+                if rest.len() == 64 || rest.len() == 65 {
                     return Ok(Self::Mint(Mint{
-                        amount: Self::unpack_amount(rest)?,
+                        amount: Self::unpack_amount(&rest[..8])?,
+                        sequence: Self::unpack_amount(&rest[8..])?,
+                        eth_tx_hash: *array_ref![rest, 16, 32],
+                        eth_log_index: Self::unpack_amount(&rest[48..])?,
+                        eth_block_number: Self::unpack_amount(&rest[56..])?,
+                        expected_spl_decimals: rest.get(64).copied(),
                     }));
                 }
                 Err(LockerError::InvalidInstruction.into())
             }
-            4 => {
-                if rest.len() >= 8 + DESTINATION_CHAIN_ADDRESS_LEN {
+            4 | 24 => {
+                // exactly one optional byte after the destination carries
+                // the expected decimals, longer payloads are only padding
+                let len = 8 + DESTINATION_CHAIN_ADDRESS_LEN;
+                if Self::payload_fits(rest, len) || rest.len() == len + 1 {
                     let src = array_ref![rest, 0, 8 + DESTINATION_CHAIN_ADDRESS_LEN];
                     let (
                         amount,
@@ -106,14 +316,265 @@ impl LockerInstruction {
                     return Ok(Self::BurnAndRelease(BurnAndRelease{
                         amount: u64::from_le_bytes(*amount),
                         destination: *destination,
+                        close_account: tag == 24,
+                        expected_spl_decimals: if rest.len() == len + 1 { Some(rest[len]) } else { None },
+                    }));
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            5 => {
+                if rest.len() == 8 {
+                    return Ok(Self::SetEpochMintCap(SetEpochMintCap{
+                        cap: Self::unpack_amount(rest)?,
                     }));
                 }
                 Err(LockerError::InvalidInstruction.into())
             }
+            6 => {
+                Ok(Self::ReadLog(ReadLog{
+                    direction: Self::unpack_direction(rest)?,
+                }))
+            }
+            7 => {
+                if rest.len() == 8 {
+                    return Ok(Self::SetDepositCap(SetDepositCap{
+                        cap: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            8 => {
+                if rest.len() == 32 {
+                    return Ok(Self::SetBackupAuthority(SetBackupAuthority{
+                        backup_authority: Pubkey::new_from_array(*array_ref![rest, 0, 32]),
+                    }));
+                }
+                Err(LockerError::InvalidAuthority.into())
+            }
+            9 => {
+                if Self::payload_fits(rest, 0) {
+                    return Ok(Self::ClaimMintAuthority);
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            10 => {
+                if rest.len() == 8 {
+                    return Ok(Self::AdminBurn(AdminBurn{
+                        amount: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            11 => {
+                if rest.len() == 8 {
+                    return Ok(Self::SetLargeReleaseThreshold(SetLargeReleaseThreshold{
+                        threshold: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            12 => {
+                if Self::payload_fits(rest, 0) {
+                    return Ok(Self::CreateReleaseRecord);
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            13 => {
+                if Self::payload_fits(rest, 0) {
+                    return Ok(Self::GetInFlight);
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            14 => {
+                if rest.len() == 40 {
+                    return Ok(Self::PreviewRelease(PreviewRelease{
+                        amount: Self::unpack_amount(&rest[..8])?,
+                        destination: Pubkey::new_from_array(*array_ref![rest, 8, 32]),
+                    }));
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            15 => {
+                Ok(Self::ResizeLogAccount(ResizeLogAccount{
+                    direction: Self::unpack_direction(rest)?,
+                }))
+            }
+            16 => {
+                if Self::payload_fits(rest, 0) {
+                    return Ok(Self::GetHealth);
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            17 => {
+                if rest.len() == 88 {
+                    return Ok(Self::RecordClaim(RecordClaim{
+                        amount: Self::unpack_amount(&rest[..8])?,
+                        sequence: Self::unpack_amount(&rest[8..])?,
+                        recipient: Pubkey::new_from_array(*array_ref![rest, 16, 32]),
+                        eth_tx_hash: *array_ref![rest, 48, 32],
+                        eth_log_index: Self::unpack_amount(&rest[80..])?,
+                    }));
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            18 => {
+                if rest.len() == 40 {
+                    return Ok(Self::ClaimMint(ClaimMint{
+                        eth_tx_hash: *array_ref![rest, 0, 32],
+                        eth_log_index: Self::unpack_amount(&rest[32..])?,
+                    }));
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            19 => {
+                if rest.len() == 8 {
+                    return Ok(Self::SetMaxMintPerTx(SetMaxMintPerTx{
+                        max: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            20 => {
+                if Self::payload_fits(rest, 0) {
+                    return Ok(Self::GetRoles);
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            21 => {
+                if Self::payload_fits(rest, 0) {
+                    return Ok(Self::ApplyConfigChange);
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            22 => {
+                if rest.len() == ETH_ADDRESS_LEN {
+                    return Ok(Self::SetEthContract(SetEthContract{
+                        eth_contract: *array_ref![rest, 0, ETH_ADDRESS_LEN],
+                    }));
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            23 => {
+                if Self::payload_fits(rest, 0) {
+                    return Ok(Self::GetEthContract);
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            25 => {
+                if rest.len() == 48 {
+                    return Ok(Self::StartRelease(StartRelease{
+                        amount: Self::unpack_amount(&rest[..8])?,
+                        sequence: Self::unpack_amount(&rest[8..])?,
+                        destination: Pubkey::new_from_array(*array_ref![rest, 16, 32]),
+                    }));
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            26 => {
+                if rest.len() == 16 {
+                    return Ok(Self::FillRelease(FillRelease{
+                        sequence: Self::unpack_amount(&rest[..8])?,
+                        amount: Self::unpack_amount(&rest[8..])?,
+                    }));
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            27 => {
+                if rest.len() == 32 {
+                    return Ok(Self::SetReleaseRoot(SetReleaseRoot{
+                        root: *array_ref![rest, 0, 32],
+                    }));
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            28 => {
+                // amount and burn id, then the proof one 32 byte node at a time
+                if rest.len() >= 16 && (rest.len() - 16) % 32 == 0 {
+                    return Ok(Self::ClaimReleaseWithProof(ClaimReleaseWithProof{
+                        amount: Self::unpack_amount(&rest[..8])?,
+                        burn_id: Self::unpack_amount(&rest[8..])?,
+                        proof: rest[16..].chunks(32).map(|node| *array_ref![node, 0, 32]).collect(),
+                    }));
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            29 => {
+                if Self::payload_fits(rest, 0) {
+                    return Ok(Self::CloseRecipientAccount);
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            30 => {
+                let enabled = match rest {
+                    [0] => false,
+                    [1] => true,
+                    _ => return Err(LockerError::InvalidInstruction.into()),
+                };
+                Ok(Self::SetLocalMode(SetLocalMode{
+                    enabled,
+                }))
+            }
+            31 => {
+                if Self::payload_fits(rest, 0) {
+                    return Ok(Self::DumpState);
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            33 => {
+                // a byte flagging which limits are present, then all four in order
+                if let Some((&present, values)) = rest.split_first() {
+                    if present < 1 << 4 && values.len() == 32 {
+                        let field = |index: usize| -> Result<Option<u64>, ProgramError> {
+                            if present & (1 << index) == 0 {
+                                return Ok(None);
+                            }
+                            Ok(Some(Self::unpack_amount(&values[index * 8..])?))
+                        };
+                        return Ok(Self::UpdateConfig(UpdateConfig{
+                            epoch_mint_cap: field(0)?,
+                            deposit_cap: field(1)?,
+                            large_release_threshold: field(2)?,
+                            max_mint_per_tx: field(3)?,
+                        }));
+                    }
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            34 => {
+                if Self::payload_fits(rest, 0) {
+                    return Ok(Self::GetMetadata);
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
+            35 => {
+                if Self::payload_fits(rest, 0) {
+                    return Ok(Self::MigrateState);
+                }
+                Err(LockerError::InvalidInstruction.into())
+            }
             _ => Err(ProgramError::InvalidInstructionData.into()),
         }
     }
 
+    /// Whether `input` holds a `len` byte payload. Trailing bytes are
+    /// ignored for backward compatibility unless the `strict-parsing`
+    /// feature is enabled, in which case the length must match exactly.
+    fn payload_fits(input: &[u8], len: usize) -> bool {
+        if cfg!(feature = "strict-parsing") {
+            input.len() == len
+        } else {
+            input.len() >= len
+        }
+    }
+
+    fn unpack_direction(input: &[u8]) -> Result<LogDirection, ProgramError> {
+        match input {
+            [0] => Ok(LogDirection::LockAndMint),
+            [1] => Ok(LogDirection::BurnAndRelease),
+            _ => Err(LockerError::InvalidInstruction.into()),
+        }
+    }
+
     fn unpack_amount(input: &[u8]) -> Result<u64, ProgramError> {
         let amount = input
             .get(..8)
@@ -122,4 +583,129 @@ impl LockerInstruction {
             .ok_or(LockerError::InvalidInstruction)?;
         Ok(amount)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_trailing_byte(data: &[u8], expected: LockerInstruction) {
+        let mut extended = data.to_vec();
+        extended.push(0);
+        assert_eq!(LockerInstruction::unpack(data).unwrap(), expected);
+        if cfg!(feature = "strict-parsing") {
+            assert_eq!(
+                LockerInstruction::unpack(&extended),
+                Err(LockerError::InvalidInstruction.into())
+            );
+        } else {
+            assert_eq!(LockerInstruction::unpack(&extended).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        let destination = [7u8; DESTINATION_CHAIN_ADDRESS_LEN];
+        let mut data = vec![1u8];
+        data.extend_from_slice(&100u64.to_le_bytes());
+        data.extend_from_slice(&destination);
+        assert_trailing_byte(&data, LockerInstruction::LockAndMint(LockandMint {
+            amount: 100,
+            destination,
+        }));
+
+        // a single trailing byte is the expected decimals, so padding starts
+        // with the second one and carries no decimals
+        for &(tag, close_account) in [(4u8, false), (24, true)].iter() {
+            data[0] = tag;
+            data.truncate(1 + 8 + DESTINATION_CHAIN_ADDRESS_LEN);
+            data.push(9);
+            let burn = |expected_spl_decimals| LockerInstruction::BurnAndRelease(BurnAndRelease {
+                amount: 100,
+                destination,
+                close_account,
+                expected_spl_decimals,
+            });
+            assert_eq!(LockerInstruction::unpack(&data).unwrap(), burn(Some(9)));
+            data.push(0);
+            if cfg!(feature = "strict-parsing") {
+                assert_eq!(LockerInstruction::unpack(&data), Err(LockerError::InvalidInstruction.into()));
+            } else {
+                assert_eq!(LockerInstruction::unpack(&data).unwrap(), burn(None));
+            }
+        }
+
+        assert_trailing_byte(&[9], LockerInstruction::ClaimMintAuthority);
+        assert_trailing_byte(&[12], LockerInstruction::CreateReleaseRecord);
+        assert_trailing_byte(&[13], LockerInstruction::GetInFlight);
+        assert_trailing_byte(&[16], LockerInstruction::GetHealth);
+        assert_trailing_byte(&[20], LockerInstruction::GetRoles);
+        assert_trailing_byte(&[21], LockerInstruction::ApplyConfigChange);
+        assert_trailing_byte(&[23], LockerInstruction::GetEthContract);
+        assert_trailing_byte(&[29], LockerInstruction::CloseRecipientAccount);
+        assert_trailing_byte(&[31], LockerInstruction::DumpState);
+        assert_trailing_byte(&[34], LockerInstruction::GetMetadata);
+        assert_trailing_byte(&[35], LockerInstruction::MigrateState);
+    }
+
+    #[test]
+    fn test_mint_eth_source_fields() {
+        let mut data = vec![3u8];
+        data.extend_from_slice(&100u64.to_le_bytes());
+        data.extend_from_slice(&7u64.to_le_bytes());
+        data.extend_from_slice(&[0x11u8; 32]);
+        data.extend_from_slice(&3u64.to_le_bytes());
+        data.extend_from_slice(&15_000_000u64.to_le_bytes());
+        assert_eq!(
+            LockerInstruction::unpack(&data).unwrap(),
+            LockerInstruction::Mint(Mint {
+                amount: 100,
+                sequence: 7,
+                eth_tx_hash: [0x11u8; 32],
+                eth_log_index: 3,
+                eth_block_number: 15_000_000,
+                expected_spl_decimals: None,
+            })
+        );
+        assert_eq!(
+            LockerInstruction::unpack(&data[..17]),
+            Err(LockerError::InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn test_expected_decimals() {
+        let mut data = vec![3u8];
+        data.extend_from_slice(&[0u8; 64]);
+        match LockerInstruction::unpack(&data).unwrap() {
+            LockerInstruction::Mint(mint) => assert_eq!(mint.expected_spl_decimals, None),
+            instruction => panic!("unexpected {:?}", instruction),
+        }
+        data.push(6);
+        match LockerInstruction::unpack(&data).unwrap() {
+            LockerInstruction::Mint(mint) => assert_eq!(mint.expected_spl_decimals, Some(6)),
+            instruction => panic!("unexpected {:?}", instruction),
+        }
+        data.push(0);
+        assert_eq!(LockerInstruction::unpack(&data), Err(LockerError::InvalidInstruction.into()));
+
+        let mut data = vec![4u8];
+        data.extend_from_slice(&[0u8; 8 + DESTINATION_CHAIN_ADDRESS_LEN]);
+        match LockerInstruction::unpack(&data).unwrap() {
+            LockerInstruction::BurnAndRelease(burn) => assert_eq!(burn.expected_spl_decimals, None),
+            instruction => panic!("unexpected {:?}", instruction),
+        }
+    }
+
+    #[test]
+    fn test_instruction_version() {
+        let tag = (INSTRUCTION_VERSION << TAG_BITS) | 13;
+        assert_eq!(LockerInstruction::unpack(&[tag]).unwrap(), LockerInstruction::GetInFlight);
+
+        let tag = ((INSTRUCTION_VERSION + 1) << TAG_BITS) | 13;
+        assert_eq!(
+            LockerInstruction::unpack(&[tag]),
+            Err(LockerError::UnsupportedInstructionVersion.into())
+        );
+    }
+}
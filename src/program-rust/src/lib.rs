@@ -1,9 +1,11 @@
 
+pub mod builder;
 pub mod error;
 pub mod instruction;
 pub mod processor;
 pub mod state;
 pub mod types;
+pub mod wire;
 
 use solana_program::{
     account_info::{AccountInfo},
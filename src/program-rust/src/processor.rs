@@ -1,10 +1,14 @@
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
+    keccak,
+    log::sol_log_data,
     msg,
     pubkey::Pubkey,
-    program::{invoke, invoke_signed},
+    program::{invoke, invoke_signed, set_return_data},
     program_error::ProgramError,
+    program_option::COption,
     program_pack::{IsInitialized, Pack},
     system_instruction,
     system_program,
@@ -12,17 +16,29 @@ use solana_program::{
     sysvar::Sysvar,
 };
 use spl_math::uint::U256;
-use spl_token;
+use spl_token::{self, error::TokenError};
 use std::convert::TryInto;
 
 use crate::{error::LockerError, 
     instruction, 
     instruction::LockerInstruction, 
+    instruction::LogDirection, 
     state, state::Locker, 
     state::BurnAndReleaseLog, 
-    state::LockAndMintLog
+    state::Claim,
+    state::ConfigChange,
+    state::ConfigLimits,
+    state::LockAndMintLog,
+    state::HealthSummary,
+    state::InstructionResult,
+    state::LockReceipt,
+    state::MintReceipt,
+    state::PendingRelease,
+    state::ReleaseRecord,
+    state::RolesView,
+    wire,
 };
-use crate::types::DESTINATION_CHAIN_ADDRESS_LEN;
+use crate::types::{mpl_token_metadata, DESTINATION_CHAIN_ADDRESS_LEN, ETH_ADDRESS_LEN, MAX_DECIMALS};
 
 pub struct Processor;
 impl Processor {
@@ -30,25 +46,177 @@ impl Processor {
         let instruction = LockerInstruction::unpack(instruction_data)?;
 
         match instruction {
-            LockerInstruction::Initialize(instruction::Initialize{authority}) => {
+            LockerInstruction::Initialize(instruction::Initialize{authority, backup_authority, metadata, underlying_decimals}) => {
                 msg!("Instruction: InitEscrow");
-                Self::process_init_locker(accounts, authority, program_id)
+                Self::process_init_locker(accounts, authority, backup_authority, metadata, underlying_decimals, program_id)
             }
             LockerInstruction::LockAndMint(instruction::LockandMint{amount, destination}) => {
                 msg!("Instruction: LockAndMint");
                 Self::process_lock_and_mint(accounts, amount, destination, program_id)
             }
-            LockerInstruction::Release(instruction::Release{amount}) => {
+            LockerInstruction::Release(instruction::Release{amount, sequence}) => {
                 msg!("Instruction: Release");
-                Self::process_release(accounts, amount, program_id)
+                Self::process_release(accounts, amount, sequence, program_id)
             }
-            LockerInstruction::Mint(instruction::Mint{amount}) => {
+            LockerInstruction::Mint(instruction::Mint{
+                amount,
+                sequence,
+                eth_tx_hash,
+                eth_log_index,
+                eth_block_number,
+                expected_spl_decimals,
+            }) => {
                 msg!("Instruction: Mint");
-                Self::process_mint(accounts, amount, program_id)
+                let receipt = MintReceipt {
+                    amount,
+                    sequence,
+                    eth_tx_hash,
+                    eth_log_index,
+                    eth_block_number,
+                    ..MintReceipt::default()
+                };
+                Self::process_mint(accounts, receipt, expected_spl_decimals, program_id)
             }
-            LockerInstruction::BurnAndRelease(instruction::BurnAndRelease{amount, destination}) => {
+            LockerInstruction::BurnAndRelease(instruction::BurnAndRelease{
+                amount,
+                destination,
+                close_account,
+                expected_spl_decimals,
+            }) => {
                 msg!("Instruction: BurnAndRelease");
-                Self::process_burn_and_release(accounts, amount, destination, program_id)
+                Self::process_burn_and_release(
+                    accounts,
+                    amount,
+                    destination,
+                    close_account,
+                    expected_spl_decimals,
+                    program_id
+                )
+            }
+            LockerInstruction::SetEpochMintCap(instruction::SetEpochMintCap{cap}) => {
+                msg!("Instruction: SetEpochMintCap");
+                Self::process_queue_config_change(accounts, ConfigChange::EpochMintCap(cap), program_id)
+            }
+            LockerInstruction::ReadLog(instruction::ReadLog{direction}) => {
+                msg!("Instruction: ReadLog");
+                Self::process_read_log(accounts, direction, program_id)
+            }
+            LockerInstruction::SetDepositCap(instruction::SetDepositCap{cap}) => {
+                msg!("Instruction: SetDepositCap");
+                Self::process_queue_config_change(accounts, ConfigChange::DepositCap(cap), program_id)
+            }
+            LockerInstruction::SetBackupAuthority(instruction::SetBackupAuthority{backup_authority}) => {
+                msg!("Instruction: SetBackupAuthority");
+                Self::process_queue_config_change(accounts, ConfigChange::BackupAuthority(backup_authority), program_id)
+            }
+            LockerInstruction::ClaimMintAuthority => {
+                msg!("Instruction: ClaimMintAuthority");
+                Self::process_claim_mint_authority(accounts, program_id)
+            }
+            LockerInstruction::AdminBurn(instruction::AdminBurn{amount}) => {
+                msg!("Instruction: AdminBurn");
+                Self::process_admin_burn(accounts, amount, program_id)
+            }
+            LockerInstruction::SetLargeReleaseThreshold(instruction::SetLargeReleaseThreshold{threshold}) => {
+                msg!("Instruction: SetLargeReleaseThreshold");
+                Self::process_queue_config_change(accounts, ConfigChange::LargeReleaseThreshold(threshold), program_id)
+            }
+            LockerInstruction::CreateReleaseRecord => {
+                msg!("Instruction: CreateReleaseRecord");
+                Self::process_create_release_record(accounts, program_id)
+            }
+            LockerInstruction::GetInFlight => {
+                msg!("Instruction: GetInFlight");
+                Self::process_get_in_flight(accounts, program_id)
+            }
+            LockerInstruction::PreviewRelease(instruction::PreviewRelease{amount, destination}) => {
+                msg!("Instruction: PreviewRelease");
+                Self::process_preview_release(accounts, amount, &destination, program_id)
+            }
+            LockerInstruction::ResizeLogAccount(instruction::ResizeLogAccount{direction}) => {
+                msg!("Instruction: ResizeLogAccount");
+                Self::process_resize_log_account(accounts, direction, program_id)
+            }
+            LockerInstruction::MigrateState => {
+                msg!("Instruction: MigrateState");
+                Self::process_migrate_state(accounts, program_id)
+            }
+            LockerInstruction::GetHealth => {
+                msg!("Instruction: GetHealth");
+                Self::process_get_health(accounts, program_id)
+            }
+            LockerInstruction::RecordClaim(instruction::RecordClaim{amount, sequence, recipient, eth_tx_hash, eth_log_index}) => {
+                msg!("Instruction: RecordClaim");
+                let claim = Claim {
+                    is_initialized: true,
+                    claimed: false,
+                    recipient,
+                    amount,
+                    sequence,
+                    eth_tx_hash,
+                    eth_log_index,
+                };
+                Self::process_record_claim(accounts, claim, program_id)
+            }
+            LockerInstruction::ClaimMint(instruction::ClaimMint{eth_tx_hash, eth_log_index}) => {
+                msg!("Instruction: ClaimMint");
+                Self::process_claim_mint(accounts, &eth_tx_hash, eth_log_index, program_id)
+            }
+            LockerInstruction::SetMaxMintPerTx(instruction::SetMaxMintPerTx{max}) => {
+                msg!("Instruction: SetMaxMintPerTx");
+                Self::process_queue_config_change(accounts, ConfigChange::MaxMintPerTx(max), program_id)
+            }
+            LockerInstruction::GetRoles => {
+                msg!("Instruction: GetRoles");
+                Self::process_get_roles(accounts, program_id)
+            }
+            LockerInstruction::ApplyConfigChange => {
+                msg!("Instruction: ApplyConfigChange");
+                Self::process_apply_config_change(accounts, program_id)
+            }
+            LockerInstruction::SetEthContract(instruction::SetEthContract{eth_contract}) => {
+                msg!("Instruction: SetEthContract");
+                Self::process_queue_config_change(accounts, ConfigChange::EthContract(eth_contract), program_id)
+            }
+            LockerInstruction::GetEthContract => {
+                msg!("Instruction: GetEthContract");
+                Self::process_get_eth_contract(accounts, program_id)
+            }
+            LockerInstruction::StartRelease(instruction::StartRelease{amount, sequence, destination}) => {
+                msg!("Instruction: StartRelease");
+                Self::process_start_release(accounts, amount, sequence, destination, program_id)
+            }
+            LockerInstruction::FillRelease(instruction::FillRelease{sequence, amount}) => {
+                msg!("Instruction: FillRelease");
+                Self::process_fill_release(accounts, sequence, amount, program_id)
+            }
+            LockerInstruction::SetReleaseRoot(instruction::SetReleaseRoot{root}) => {
+                msg!("Instruction: SetReleaseRoot");
+                Self::process_set_release_root(accounts, root, program_id)
+            }
+            LockerInstruction::ClaimReleaseWithProof(instruction::ClaimReleaseWithProof{amount, burn_id, proof}) => {
+                msg!("Instruction: ClaimReleaseWithProof");
+                Self::process_claim_release_with_proof(accounts, amount, burn_id, &proof, program_id)
+            }
+            LockerInstruction::CloseRecipientAccount => {
+                msg!("Instruction: CloseRecipientAccount");
+                Self::process_close_recipient_account(accounts, program_id)
+            }
+            LockerInstruction::SetLocalMode(instruction::SetLocalMode{enabled}) => {
+                msg!("Instruction: SetLocalMode");
+                Self::process_queue_config_change(accounts, ConfigChange::LocalMode(enabled), program_id)
+            }
+            LockerInstruction::DumpState => {
+                msg!("Instruction: DumpState");
+                Self::process_dump_state(accounts, program_id)
+            }
+            LockerInstruction::UpdateConfig(update) => {
+                msg!("Instruction: UpdateConfig");
+                Self::process_update_config(accounts, update, program_id)
+            }
+            LockerInstruction::GetMetadata => {
+                msg!("Instruction: GetMetadata");
+                Self::process_get_metadata(accounts, program_id)
             }
         }
     }
@@ -56,6 +224,9 @@ impl Processor {
     fn process_init_locker(
         accounts: &[AccountInfo],
         authority: Pubkey,
+        backup_authority: Pubkey,
+        metadata: Pubkey,
+        underlying_decimals: u8,
         program_id: &Pubkey,
     ) -> ProgramResult {  
         let account_info_iter = &mut accounts.iter();
@@ -63,6 +234,9 @@ impl Processor {
         if !initializer_info.is_signer {
             return Err(ProgramError::MissingRequiredSignature);
         }
+        if underlying_decimals > MAX_DECIMALS {
+            return Err(LockerError::InvalidDecimals.into());
+        }
 
         let state_account_info = next_account_info(account_info_iter)?;
         let mintlog_account_info = next_account_info(account_info_iter)?;
@@ -78,16 +252,43 @@ impl Processor {
             return Err(ProgramError::InvalidAccountData);
         }
 
+        // the initializer funds all three accounts, check up front rather
+        // than failing inside the second or third create_account
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let total_init_rent = rent.minimum_balance(state::LOGSIZE)
+            .checked_mul(2)
+            .and_then(|logs_rent| logs_rent.checked_add(rent.minimum_balance(state::STATESIZE)))
+            .ok_or(LockerError::ArithmeticOverflow)?;
+        if initializer_info.lamports() < total_init_rent {
+            msg!("Initializer holds {} lamports, {} needed for rent", initializer_info.lamports(), total_init_rent);
+            return Err(LockerError::InsufficientFundsForInit.into());
+        }
+
+        // a metadata pointer must name a live metadata account, so clients
+        // never follow it to something the metadata program does not own
+        if metadata != Pubkey::default() {
+            let metadata_info = next_account_info(account_info_iter)?;
+            if !metadata_info.key.eq(&metadata) || !metadata_info.owner.eq(&mpl_token_metadata::id()) {
+                return Err(LockerError::InvalidMetadataAccount.into());
+            }
+        }
+
         msg!("Creating state account pubkey");
-        let (state_account_pubkey, nonce) = Pubkey::find_program_address(&[b"Locker", b"Init"], program_id);
-        if !(state_account_info.key.eq(&state_account_pubkey)) {
-            return Err(ProgramError::InvalidAccountData);
+        let nonce = Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+        // create_account would refuse an existing state account too, but
+        // only by accident, and a second Initialize must never get as far
+        // as packing a new authority
+        if state_account_info.owner.eq(program_id) && state_account_info.data_len() == state::STATESIZE {
+            let initialized = Locker::unpack_unchecked(&state_account_info.data.borrow())
+                .map_or(false, |state_info| state_info.is_initialized());
+            if initialized {
+                return Err(LockerError::AlreadyInitialized.into());
+            }
         }
 
-        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
         let mut required_balance = rent.minimum_balance(state::STATESIZE);
 
-        let create_state_account_ix = system_instruction::create_account(initializer_info.key, &state_account_pubkey, required_balance, state::STATESIZE as u64, program_id);
+        let create_state_account_ix = system_instruction::create_account(initializer_info.key, state_account_info.key, required_balance, state::STATESIZE as u64, program_id);
 
 
         msg!("submitting tx to create program derived state account");
@@ -101,17 +302,14 @@ impl Processor {
             ],
             &[&[&b"Locker"[..], &b"Init"[..], &[nonce]]],
         )?;
-        msg!("state account pubkey: {}", state_account_pubkey);
+        msg!("state account pubkey: {}", state_account_info.key);
 
         msg!("Creating lock and mint log account pubkey");
-        let (mintlog_account_pubkey, nonce) = Pubkey::find_program_address(&[b"Locker", b"Mint"], program_id);
-        if !(mintlog_account_info.key.eq(&mintlog_account_pubkey)) {
-            return Err(ProgramError::InvalidAccountData);
-        }
+        let nonce = Self::verify_pda(mintlog_account_info, &[b"Locker", b"Mint"], program_id)?;
 
         required_balance = rent.minimum_balance(state::LOGSIZE);
 
-        let create_state_account_ix = system_instruction::create_account(initializer_info.key, &mintlog_account_pubkey, required_balance, state::LOGSIZE as u64, program_id);
+        let create_state_account_ix = system_instruction::create_account(initializer_info.key, mintlog_account_info.key, required_balance, state::LOGSIZE as u64, program_id);
 
 
         msg!("submitting tx to create program derived state account");
@@ -125,16 +323,13 @@ impl Processor {
             ],
             &[&[&b"Locker"[..], &b"Mint"[..], &[nonce]]],
         )?;
-        msg!("mintlog account pubkey: {}", state_account_pubkey);
+        msg!("mintlog account pubkey: {}", state_account_info.key);
 
         msg!("Creating state account pubkey");
-        let (burnlog_account_pubkey, nonce) = Pubkey::find_program_address(&[b"Locker", b"Burn"], program_id);
-        if !(burnlog_account_info.key.eq(&burnlog_account_pubkey)) {
-            return Err(ProgramError::InvalidAccountData);
-        }
+        let nonce = Self::verify_pda(burnlog_account_info, &[b"Locker", b"Burn"], program_id)?;
 
         let create_state_account_ix = system_instruction::create_account(initializer_info.key,
-            &burnlog_account_pubkey,
+            burnlog_account_info.key,
             required_balance,
             state::LOGSIZE as u64,
             program_id
@@ -151,17 +346,38 @@ impl Processor {
             ],
             &[&[&b"Locker"[..], &b"Burn"[..], &[nonce]]],
         )?;
-        msg!("burn log account pubkey: {}", burnlog_account_pubkey);
+        msg!("burn log account pubkey: {}", burnlog_account_info.key);
 
-        Locker::pack(
-            Locker{
-                is_initialized: true,
-                authority: authority,
-                total_locked: 0,
-                total_minted: 0
-            }, 
-            &mut state_account_info.data.borrow_mut()
-        )?;
+        let state_info = Locker{
+            is_initialized: true,
+            authority: authority,
+            total_locked: 0,
+            total_minted: 0,
+            epoch_mint_cap: 0,
+            current_epoch: 0,
+            epoch_minted: 0,
+            total_locked_cap: 0,
+            in_progress: false,
+            collateralization_ratio: u64::MAX,
+            backup_authority,
+            large_release_threshold: 0,
+            max_mint_per_tx: 0,
+            pending_change: None,
+            pending_change_slot: 0,
+            eth_contract: [0u8; ETH_ADDRESS_LEN],
+            release_root: [0u8; 32],
+            local_mode: false,
+            metadata,
+            outbound_nonce: 0,
+            // recorded from the mint itself once the first mint goes through
+            mint_decimals: spl_token::native_mint::DECIMALS,
+            underlying_decimals,
+            mint: Pubkey::default(),
+        };
+        Locker::pack(state_info, &mut state_account_info.data.borrow_mut())?;
+        LockAndMintLog::pack(LockAndMintLog::default(), &mut mintlog_account_info.data.borrow_mut())?;
+        BurnAndReleaseLog::pack(BurnAndReleaseLog::default(), &mut burnlog_account_info.data.borrow_mut())?;
+        Self::set_result(0, &state_info)?;
 
         Ok(())
     }
@@ -179,27 +395,45 @@ impl Processor {
         }
 
         let state_account_info = next_account_info(account_info_iter)?;
-        let (state_account_pubkey, _) = Pubkey::find_program_address(&[b"Locker", b"Init"], program_id);
-        if !(state_account_info.key.eq(&state_account_pubkey)) {
-            return Err(ProgramError::InvalidAccountData);
-        }
+        let nonce = Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
 
         let mintlog_account_info = next_account_info(account_info_iter)?;
-        let (mintlog_account_pubkey, _) = Pubkey::find_program_address(&[b"Locker", b"Mint"], program_id);
-        if !(mintlog_account_info.key.eq(&mintlog_account_pubkey)) {
-            return Err(ProgramError::InvalidAccountData);
-        }
+        Self::verify_pda(mintlog_account_info, &[b"Locker", b"Mint"], program_id)?;
+        Self::check_owner(mintlog_account_info, program_id)?;
+        Self::check_writable(mintlog_account_info)?;
+        Self::check_log_size(mintlog_account_info)?;
 
         let system_program_info = next_account_info(account_info_iter)?;
         if !(system_program_info.key.eq(&system_program::id())) {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        let mut state_info = Locker::unpack_unchecked(&state_account_info.data.borrow())?; 
-        if !state_info.is_initialized(){
-            return Err(ProgramError::UninitializedAccount);
+        let mut state_info = Self::unpack_state_mut(state_account_info, program_id)?;
+        // an event for zero underlying tokens is worthless to the other side
+        let underlying_amount = Self::underlying_amount_from_spl_amount(
+            state_info.underlying_decimals,
+            spl_token::native_mint::DECIMALS,
+            amount
+        )?;
+        if underlying_amount.is_zero() {
+            return Err(LockerError::AmountTooSmall.into());
         }
-        state_info.total_locked += amount;
+        let total_locked = state_info.total_locked
+            .checked_add(amount)
+            .ok_or(LockerError::ArithmeticOverflow)?;
+        if state_info.total_locked_cap != 0 && total_locked > state_info.total_locked_cap {
+            return Err(LockerError::DepositCapExceeded.into());
+        }
+        state_info.total_locked = total_locked;
+        state_info.update_collateralization_ratio()?;
+        // a local lock is settled by its own mint and never logged, so it
+        // takes no nonce either
+        let outbound_nonce = if state_info.local_mode {
+            0
+        } else {
+            state_info.next_outbound_nonce()?
+        };
+        state_info.in_progress = true;
         Locker::pack(state_info, &mut state_account_info.data.borrow_mut())?;
 
         let transfer_lamports_ix = system_instruction::transfer(
@@ -217,10 +451,81 @@ impl Processor {
             ]
         )?;
 
-        let mut log_info = LockAndMintLog::unpack_unchecked(&mintlog_account_info.data.borrow())?;
-        log_info.amount = Self::underlying_amount_from_spl_amount(18, 9, amount)?;
-        log_info.recipient = destination;
-        LockAndMintLog::pack(log_info, &mut mintlog_account_info.data.borrow_mut())?;
+        // a local locker wraps lamports one to one into a mint on this
+        // cluster, without waiting for the Ethereum round trip
+        if state_info.local_mode {
+            let recipient_account_info = next_account_info(account_info_iter)?;
+            let minter_info = next_account_info(account_info_iter)?;
+            // anyone can lock, so only a mint the authority pinned will do
+            if !(state_info.mint.eq(minter_info.key)) {
+                return Err(LockerError::InvalidMint.into());
+            }
+            let mint = Self::unpack_mint(minter_info)?;
+            if mint.mint_authority != COption::Some(*state_account_info.key) {
+                return Err(LockerError::InvalidMint.into());
+            }
+            if mint.decimals != spl_token::native_mint::DECIMALS {
+                return Err(LockerError::InvalidDecimals.into());
+            }
+            let token_program_info = next_account_info(account_info_iter)?;
+            if !(spl_token::id().eq(token_program_info.key)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            state_info.check_mint_amount(amount)?;
+            state_info.total_minted = state_info.total_minted
+                .checked_add(amount)
+                .ok_or(LockerError::ArithmeticOverflow)?;
+            state_info.mint_decimals = mint.decimals;
+            state_info.update_collateralization_ratio()?;
+            state_info.record_epoch_mint(Clock::get()?.epoch, amount)?;
+            Locker::pack(state_info, &mut state_account_info.data.borrow_mut())?;
+
+            let mint_ix = spl_token::instruction::mint_to(
+                token_program_info.key,
+                minter_info.key,
+                recipient_account_info.key,
+                state_account_info.key,
+                &[],
+                amount
+            )?;
+            invoke_signed(
+                &mint_ix,
+                &[
+                    state_account_info.clone(),
+                    minter_info.clone(),
+                    recipient_account_info.clone(),
+                    token_program_info.clone(),
+                ],
+                &[&[&b"Locker"[..], &b"Init"[..], &[nonce]]],
+            ).map_err(|error| Self::map_token_error("mint_to", error))?;
+            msg!("minted {} locally to {}", amount, recipient_account_info.key);
+        }
+        Self::clear_in_progress(state_account_info)?;
+
+        // a relayer seeing the lock would back it on Ethereum as well
+        if !state_info.local_mode {
+            let mut log_info = LockAndMintLog::unpack_unchecked(&mintlog_account_info.data.borrow())?;
+            log_info.amount = underlying_amount;
+            log_info.recipient = destination;
+            log_info.nonce = outbound_nonce;
+            LockAndMintLog::pack(log_info, &mut mintlog_account_info.data.borrow_mut())?;
+            let mut message = [0u8; wire::MESSAGE_LEN];
+            wire::encode_lock(&log_info.amount, &log_info.recipient, log_info.nonce, &mut message);
+            Self::emit_event(&state_info, &message);
+        }
+
+        let mut receipt = [0u8; state::RECEIPTSIZE];
+        LockReceipt::pack(LockReceipt {
+            amount,
+            underlying_amount,
+            recipient: destination,
+            total_locked: state_info.total_locked,
+            vault_balance: state_account_info.lamports(),
+            nonce: outbound_nonce,
+        }, &mut receipt)?;
+        Self::emit_ratio(&state_info)?;
+        Self::set_result_with(amount, &state_info, &receipt)?;
 
         Ok(())
     }
@@ -228,122 +533,440 @@ impl Processor {
     fn process_release(
         accounts: &[AccountInfo],
         amount: u64,
+        sequence: u64,
         program_id: &Pubkey
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let signer_account_info = next_account_info(account_info_iter)?;
-        if !signer_account_info.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
-        }
 
         let state_account_info = next_account_info(account_info_iter)?;
-        let (state_account_pubkey, nonce) = Pubkey::find_program_address(&[b"Locker", b"Init"], program_id);
-        if !(state_account_info.key.eq(&state_account_pubkey)) {
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+
+        let mut state_info = Self::unpack_state_mut(state_account_info, program_id)?;
+        Self::check_authority(accounts, &state_info, signer_account_info)?;
+        Self::check_large_release(accounts, &state_info, amount)?;
+        state_info.total_locked = state_info.total_locked
+            .checked_sub(amount)
+            .ok_or(LockerError::InsufficientLocked)?;
+        state_info.update_collateralization_ratio()?;
+        state_info.in_progress = true;
+        Locker::pack(state_info, &mut state_account_info.data.borrow_mut())?;
+
+        let destination_info = next_account_info(account_info_iter)?;
+        // the state PDA is the vault, paying it back to itself would
+        // release nothing while still debiting total_locked
+        if destination_info.key.eq(state_account_info.key) {
+            return Err(LockerError::InvalidDestination.into());
+        }
+
+        let system_program_info = next_account_info(account_info_iter)?;
+        if !(system_program_info.key.eq(&system_program::id())) {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        let mut state_info = Locker::unpack_unchecked(&state_account_info.data.borrow())?; 
-        if !state_info.is_initialized(){
-            return Err(ProgramError::UninitializedAccount);
-        } 
-        if !(state_info.authority.eq(signer_account_info.key)) {
+        let release_record_info = next_account_info(account_info_iter)?;
+        let mut release_record = Self::load_release_record(
+            release_record_info,
+            destination_info,
+            signer_account_info,
+            system_program_info,
+            program_id
+        )?;
+        release_record.record_release(amount, Clock::get()?.slot)?;
+        ReleaseRecord::pack(release_record, &mut release_record_info.data.borrow_mut())?;
+
+        let processed_account_info = next_account_info(account_info_iter)?;
+        Self::mark_processed(
+            processed_account_info,
+            signer_account_info,
+            system_program_info,
+            sequence,
+            program_id
+        )?;
+
+        Self::transfer_from_vault(state_account_info, destination_info, amount, program_id)?;
+        Self::clear_in_progress(state_account_info)?;
+        Self::emit_ratio(&state_info)?;
+        Self::set_result(amount, &state_info)?;
+
+        Ok(())
+    }
+
+    /// Records a release of `amount` to `destination` that `FillRelease`
+    /// pays out in parts, for amounts too large to settle at once. The
+    /// large release threshold applies to the whole obligation here.
+    fn process_start_release(
+        accounts: &[AccountInfo],
+        amount: u64,
+        sequence: u64,
+        destination: Pubkey,
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_account_info = next_account_info(account_info_iter)?;
+
+        let state_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+        let state_info = Self::unpack_state(state_account_info, program_id)?;
+        Self::check_authority(accounts, &state_info, signer_account_info)?;
+        Self::check_large_release(accounts, &state_info, amount)?;
+        if destination.eq(state_account_info.key) {
+            return Err(LockerError::InvalidDestination.into());
+        }
+
+        let pending_release_info = next_account_info(account_info_iter)?;
+        let sequence_bytes = sequence.to_le_bytes();
+        let pending_release_nonce = Self::verify_pda(
+            pending_release_info,
+            &[b"Locker", b"Release", &sequence_bytes],
+            program_id
+        )?;
+        Self::check_writable(pending_release_info)?;
+
+        let processed_account_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        if !(system_program_info.key.eq(&system_program::id())) {
             return Err(ProgramError::InvalidAccountData);
         }
-        state_info.total_locked -= amount;
-        Locker::pack(state_info, &mut state_account_info.data.borrow_mut())?;
+        Self::mark_processed(
+            processed_account_info,
+            signer_account_info,
+            system_program_info,
+            sequence,
+            program_id
+        )?;
+
+        if pending_release_info.data_is_empty() {
+            let rent = Rent::get()?;
+            let create_pending_release_ix = system_instruction::create_account(
+                signer_account_info.key,
+                pending_release_info.key,
+                rent.minimum_balance(state::PENDING_RELEASE_SIZE),
+                state::PENDING_RELEASE_SIZE as u64,
+                program_id
+            );
+            invoke_signed(
+                &create_pending_release_ix,
+                &[
+                    signer_account_info.clone(),
+                    pending_release_info.clone(),
+                    system_program_info.clone(),
+                ],
+                &[&[&b"Locker"[..], &b"Release"[..], &sequence_bytes, &[pending_release_nonce]]],
+            )?;
+        } else if !(pending_release_info.owner.eq(program_id)) {
+            return Err(LockerError::InvalidAccountOwner.into());
+        }
+        PendingRelease::pack(PendingRelease {
+            is_initialized: true,
+            destination,
+            total: amount,
+            filled: 0,
+            sequence,
+        }, &mut pending_release_info.data.borrow_mut())?;
+        msg!("release {} of {} started for {}", sequence, amount, destination);
+        Self::set_result(amount, &state_info)?;
+
+        Ok(())
+    }
+
+    /// Pays `amount` towards a release recorded by `StartRelease`.
+    fn process_fill_release(
+        accounts: &[AccountInfo],
+        sequence: u64,
+        amount: u64,
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_account_info = next_account_info(account_info_iter)?;
+
+        let state_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+        let mut state_info = Self::unpack_state_mut(state_account_info, program_id)?;
+        Self::check_authority(accounts, &state_info, signer_account_info)?;
+
+        let pending_release_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(pending_release_info, &[b"Locker", b"Release", &sequence.to_le_bytes()], program_id)?;
+        Self::check_owner(pending_release_info, program_id)?;
+        Self::check_writable(pending_release_info)?;
+        let mut pending_release = PendingRelease::unpack(&pending_release_info.data.borrow())?;
+        let filled = pending_release.filled
+            .checked_add(amount)
+            .ok_or(LockerError::ArithmeticOverflow)?;
+        if filled > pending_release.total {
+            msg!("Release {} has {} of {} filled, cannot fill {}", sequence, pending_release.filled, pending_release.total, amount);
+            return Err(LockerError::ReleaseOverfilled.into());
+        }
 
         let destination_info = next_account_info(account_info_iter)?;
+        if !(destination_info.key.eq(&pending_release.destination)) {
+            return Err(LockerError::InvalidDestination.into());
+        }
+
+        state_info.total_locked = state_info.total_locked
+            .checked_sub(amount)
+            .ok_or(LockerError::InsufficientLocked)?;
+        state_info.update_collateralization_ratio()?;
+        state_info.in_progress = true;
+        Locker::pack(state_info, &mut state_account_info.data.borrow_mut())?;
 
         let system_program_info = next_account_info(account_info_iter)?;
         if !(system_program_info.key.eq(&system_program::id())) {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        let transfer_lamports_ix = system_instruction::transfer(
-            state_account_info.key, 
-            destination_info.key, 
-            amount
-        );
-
-        invoke_signed(
-            &transfer_lamports_ix, 
-            &[
-                signer_account_info.clone(),
-                state_account_info.clone(),
-                destination_info.clone(),
-                system_program_info.clone()
-            ],
-            &[&[&b"Locker"[..], &b"Init"[..], &[nonce]]],
+        let release_record_info = next_account_info(account_info_iter)?;
+        let mut release_record = Self::load_release_record(
+            release_record_info,
+            destination_info,
+            signer_account_info,
+            system_program_info,
+            program_id
         )?;
+        release_record.record_release(amount, Clock::get()?.slot)?;
+        ReleaseRecord::pack(release_record, &mut release_record_info.data.borrow_mut())?;
+
+        pending_release.filled = filled;
+        PendingRelease::pack(pending_release, &mut pending_release_info.data.borrow_mut())?;
+
+        Self::transfer_from_vault(state_account_info, destination_info, amount, program_id)?;
+        Self::clear_in_progress(state_account_info)?;
+        msg!("release {} filled {} of {}", sequence, filled, pending_release.total);
+        Self::emit_ratio(&state_info)?;
+        Self::set_result(amount, &state_info)?;
 
         Ok(())
     }
 
-    fn process_mint(
+    /// Posts the Merkle root of a batch of releases, replacing the previous
+    /// batch. Leaves already claimed stay claimed.
+    fn process_set_release_root(
         accounts: &[AccountInfo],
-        amount: u64,
+        root: [u8; 32],
         program_id: &Pubkey
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let signer_account_info = next_account_info(account_info_iter)?;
-        if !signer_account_info.is_signer {
+
+        let state_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+
+        let mut state_info = Self::unpack_state_mut(state_account_info, program_id)?;
+        Self::check_authority(accounts, &state_info, signer_account_info)?;
+        state_info.release_root = root;
+        Locker::pack(state_info, &mut state_account_info.data.borrow_mut())?;
+        Self::set_result(0, &state_info)?;
+
+        Ok(())
+    }
+
+    /// Pays out one leaf of the release root. Anyone holding the proof may
+    /// submit it, the authority signed off on the whole batch when posting
+    /// the root. The burn id goes into the processed bitmap, so a burn is
+    /// paid at most once whether claimed here or released directly.
+    fn process_claim_release_with_proof(
+        accounts: &[AccountInfo],
+        amount: u64,
+        burn_id: u64,
+        proof: &[[u8; 32]],
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payer_account_info = next_account_info(account_info_iter)?;
+        if !payer_account_info.is_signer {
             return Err(ProgramError::MissingRequiredSignature);
         }
 
         let state_account_info = next_account_info(account_info_iter)?;
-        let (state_account_pubkey, _) = Pubkey::find_program_address(&[b"Locker", b"Init"], program_id);
-        if !(state_account_info.key.eq(&state_account_pubkey)) {
-            return Err(ProgramError::InvalidAccountData);
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+        let mut state_info = Self::unpack_state_mut(state_account_info, program_id)?;
+
+        let recipient_info = next_account_info(account_info_iter)?;
+        if recipient_info.key.eq(state_account_info.key) {
+            return Err(LockerError::InvalidDestination.into());
+        }
+        let leaf = Self::release_leaf(recipient_info.key, amount, burn_id);
+        if !Self::verify_release_proof(&state_info.release_root, leaf, proof) {
+            return Err(LockerError::InvalidMerkleProof.into());
         }
 
-        let mut state_info = Locker::unpack_unchecked(&state_account_info.data.borrow())?; 
-        if !state_info.is_initialized(){
-            return Err(ProgramError::UninitializedAccount);
-        } 
-        if !(state_info.authority.eq(signer_account_info.key)) {
+        state_info.total_locked = state_info.total_locked
+            .checked_sub(amount)
+            .ok_or(LockerError::InsufficientLocked)?;
+        state_info.update_collateralization_ratio()?;
+        state_info.in_progress = true;
+        Locker::pack(state_info, &mut state_account_info.data.borrow_mut())?;
+
+        let system_program_info = next_account_info(account_info_iter)?;
+        if !(system_program_info.key.eq(&system_program::id())) {
             return Err(ProgramError::InvalidAccountData);
         }
-        state_info.total_minted += amount;
-        Locker::pack(state_info, &mut state_account_info.data.borrow_mut())?;
+
+        let release_record_info = next_account_info(account_info_iter)?;
+        let mut release_record = Self::load_release_record(
+            release_record_info,
+            recipient_info,
+            payer_account_info,
+            system_program_info,
+            program_id
+        )?;
+        release_record.record_release(amount, Clock::get()?.slot)?;
+        ReleaseRecord::pack(release_record, &mut release_record_info.data.borrow_mut())?;
+
+        let processed_account_info = next_account_info(account_info_iter)?;
+        Self::mark_processed(
+            processed_account_info,
+            payer_account_info,
+            system_program_info,
+            burn_id,
+            program_id
+        )?;
+
+        Self::transfer_from_vault(state_account_info, recipient_info, amount, program_id)?;
+        Self::clear_in_progress(state_account_info)?;
+        Self::emit_ratio(&state_info)?;
+        Self::set_result(amount, &state_info)?;
+
+        Ok(())
+    }
+
+    /// Mints `receipt.amount` to the recipient token account, then fills in
+    /// the recipient and emits the receipt with `sol_log_data`.
+    fn process_mint(
+        accounts: &[AccountInfo],
+        mut receipt: MintReceipt,
+        expected_spl_decimals: Option<u8>,
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let MintReceipt { amount, sequence, eth_tx_hash, eth_log_index, .. } = receipt;
+        let account_info_iter = &mut accounts.iter();
+        let signer_account_info = next_account_info(account_info_iter)?;
+
+        let state_account_info = next_account_info(account_info_iter)?;
+        let nonce = Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+
+        let mut state_info = Self::unpack_state_mut(state_account_info, program_id)?;
+        Self::check_authority(accounts, &state_info, signer_account_info)?;
+        state_info.check_mint_amount(amount)?;
+        state_info.total_minted = state_info.total_minted
+            .checked_add(amount)
+            .ok_or(LockerError::ArithmeticOverflow)?;
+        state_info.record_epoch_mint(Clock::get()?.epoch, amount)?;
 
         let recipient_account_info = next_account_info(account_info_iter)?;
 
         let minter_info = next_account_info(account_info_iter)?;
-        if !(minter_info.owner.eq(&spl_token::id())) {
-            return Err(ProgramError::InvalidAccountData);
+        let mint = Self::unpack_mint(minter_info)?;
+        // the mint must answer to either the state PDA or the signer
+        if mint.mint_authority != COption::Some(*state_account_info.key)
+            && mint.mint_authority != COption::Some(*signer_account_info.key) {
+            return Err(LockerError::InvalidMint.into());
         }
+        Self::check_expected_decimals(expected_spl_decimals, &state_info)?;
+        // only the pinned mint gets to update the stored decimals
+        state_info.pin_mint(minter_info.key)?;
+        // lamports are locked, the mint's own decimals are minted
+        Self::assert_solvent(
+            state_info.total_locked,
+            state_info.total_minted,
+            spl_token::native_mint::DECIMALS,
+            mint.decimals
+        )?;
+        state_info.mint_decimals = mint.decimals;
+        state_info.update_collateralization_ratio()?;
+        state_info.in_progress = true;
+        Locker::pack(state_info, &mut state_account_info.data.borrow_mut())?;
 
         let token_program_info = next_account_info(account_info_iter)?;
         if !(spl_token::id().eq(token_program_info.key)) {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        let mint_ix = spl_token::instruction::mint_to(
-            token_program_info.key, 
-            minter_info.key, 
-            recipient_account_info.key, 
-            signer_account_info.key, 
-            &[signer_account_info.key],
-            amount
+        let processed_account_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        if !(system_program_info.key.eq(&system_program::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::mark_processed(
+            processed_account_info,
+            signer_account_info,
+            system_program_info,
+            sequence,
+            program_id
         )?;
-        
-        invoke(
-            &mint_ix,
-            &[
-                signer_account_info.clone(),
-                minter_info.clone(),
-                recipient_account_info.clone(),
-                token_program_info.clone(),
-            ]
+        let event_marker_info = next_account_info(account_info_iter)?;
+        Self::mark_event_processed(
+            event_marker_info,
+            signer_account_info,
+            system_program_info,
+            &eth_tx_hash,
+            eth_log_index,
+            program_id
         )?;
 
+        // once the mint authority has been handed to the state PDA the
+        // program signs for it, otherwise the signer must hold it
+        if mint.mint_authority == COption::Some(*state_account_info.key) {
+            let mint_ix = spl_token::instruction::mint_to(
+                token_program_info.key,
+                minter_info.key,
+                recipient_account_info.key,
+                state_account_info.key,
+                &[],
+                amount
+            )?;
+
+            invoke_signed(
+                &mint_ix,
+                &[
+                    state_account_info.clone(),
+                    minter_info.clone(),
+                    recipient_account_info.clone(),
+                    token_program_info.clone(),
+                ],
+                &[&[&b"Locker"[..], &b"Init"[..], &[nonce]]],
+            ).map_err(|error| Self::map_token_error("mint_to", error))?;
+        } else {
+            let mint_ix = spl_token::instruction::mint_to(
+                token_program_info.key, 
+                minter_info.key, 
+                recipient_account_info.key, 
+                signer_account_info.key, 
+                &[signer_account_info.key],
+                amount
+            )?;
+            
+            invoke(
+                &mint_ix,
+                &[
+                    signer_account_info.clone(),
+                    minter_info.clone(),
+                    recipient_account_info.clone(),
+                    token_program_info.clone(),
+                ]
+            ).map_err(|error| Self::map_token_error("mint_to", error))?;
+        }
+        Self::clear_in_progress(state_account_info)?;
+
+        receipt.recipient = *recipient_account_info.key;
+        let mut receipt_data = [0u8; state::MINT_RECEIPT_SIZE];
+        MintReceipt::pack(receipt, &mut receipt_data)?;
+        Self::emit_event(&state_info, &receipt_data);
+        Self::emit_ratio(&state_info)?;
+        Self::set_result(amount, &state_info)?;
+
         Ok(())
     }
 
+    /// Burns `amount` wrapped tokens and logs the release. With
+    /// `close_account` the owner also gets the rent of the emptied token
+    /// account back.
     fn process_burn_and_release(
         accounts: &[AccountInfo],
         amount: u64,
         destination: [u8; DESTINATION_CHAIN_ADDRESS_LEN],
+        close_account: bool,
+        expected_spl_decimals: Option<u8>,
         program_id: &Pubkey
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
@@ -353,30 +976,49 @@ impl Processor {
         }
 
         let state_account_info = next_account_info(account_info_iter)?;
-        let (state_account_pubkey, _) = Pubkey::find_program_address(&[b"Locker", b"Init"], program_id);
-        if !(state_account_info.key.eq(&state_account_pubkey)) {
-            return Err(ProgramError::InvalidAccountData);
-        }
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
 
-        let mut state_info = Locker::unpack_unchecked(&state_account_info.data.borrow())?; 
-        if !state_info.is_initialized(){
-            return Err(ProgramError::UninitializedAccount);
-        }
-        state_info.total_minted -= amount;
+        let mut state_info = Self::unpack_state_mut(state_account_info, program_id)?;
+        state_info.total_minted = state_info.total_minted
+            .checked_sub(amount)
+            .ok_or(LockerError::InsufficientMinted)?;
+        state_info.update_collateralization_ratio()?;
+        let outbound_nonce = state_info.next_outbound_nonce()?;
+        state_info.in_progress = true;
         Locker::pack(state_info, &mut state_account_info.data.borrow_mut())?;
 
         let burnlog_account_info = next_account_info(account_info_iter)?;
-        let (burnlog_account_pubkey, _) = Pubkey::find_program_address(&[b"Locker", b"Burn"], program_id);
-        if !(burnlog_account_info.key.eq(&burnlog_account_pubkey)) {
-            return Err(ProgramError::InvalidAccountData);
-        }
+        Self::verify_pda(burnlog_account_info, &[b"Locker", b"Burn"], program_id)?;
+        Self::check_owner(burnlog_account_info, program_id)?;
+        Self::check_writable(burnlog_account_info)?;
+        Self::check_log_size(burnlog_account_info)?;
 
         let token_account_info = next_account_info(account_info_iter)?;
+        if !(token_account_info.owner.eq(&spl_token::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let token_account = spl_token::state::Account::unpack(&token_account_info.data.borrow())?;
+        if token_account.amount < amount {
+            msg!("Token account holds {}, cannot burn {}", token_account.amount, amount);
+            return Err(LockerError::InsufficientTokenBalance.into());
+        }
+        if close_account {
+            // a delegate may burn, but only the owner gets to close
+            if !(token_account.owner.eq(signer_account_info.key)) {
+                return Err(LockerError::InvalidAuthority.into());
+            }
+            if token_account.amount != amount {
+                msg!("Token account keeps {} after the burn", token_account.amount.saturating_sub(amount));
+                return Err(LockerError::TokenAccountNotEmpty.into());
+            }
+        }
 
         let minter_info = next_account_info(account_info_iter)?;
         if !(minter_info.owner.eq(&spl_token::id())) {
             return Err(ProgramError::InvalidAccountData);
         }
+        state_info.check_mint(minter_info.key)?;
+        Self::check_expected_decimals(expected_spl_decimals, &state_info)?;
 
         let token_program_info = next_account_info(account_info_iter)?;
         if !(spl_token::id().eq(token_program_info.key)) {
@@ -400,49 +1042,4297 @@ impl Processor {
                 token_account_info.clone(),
                 token_program_info.clone(),
             ]
-        )?;
+        ).map_err(|error| Self::map_token_error("burn", error))?;
+        if close_account {
+            let close_ix = spl_token::instruction::close_account(
+                token_program_info.key,
+                token_account_info.key,
+                signer_account_info.key,
+                signer_account_info.key,
+                &[signer_account_info.key]
+            )?;
+
+            invoke(
+                &close_ix,
+                &[
+                    token_account_info.clone(),
+                    signer_account_info.clone(),
+                    token_program_info.clone(),
+                ]
+            ).map_err(|error| Self::map_token_error("close_account", error))?;
+        }
+        Self::clear_in_progress(state_account_info)?;
 
         let mut log_info = BurnAndReleaseLog::unpack_unchecked(&burnlog_account_info.data.borrow())?;
-        log_info.amount = Self::underlying_amount_from_spl_amount(18, 9, amount)?;
+        // the burned amount is in units of the wrapped mint
+        log_info.amount = Self::underlying_amount_from_spl_amount(
+            state_info.underlying_decimals,
+            state_info.mint_decimals,
+            amount
+        )?;
         log_info.recipient = destination;
+        log_info.nonce = outbound_nonce;
         BurnAndReleaseLog::pack(log_info, &mut burnlog_account_info.data.borrow_mut())?;
+        let mut message = [0u8; wire::MESSAGE_LEN];
+        wire::encode_burn(&log_info.amount, &log_info.recipient, log_info.nonce, &mut message);
+        Self::emit_event(&state_info, &message);
+        Self::emit_ratio(&state_info)?;
+        Self::set_result(amount, &state_info)?;
 
         Ok(())
     }
 
-    fn spl_amount_from_underlying_amount(
-        underlying_decimals: u8,
-        spl_decimals: u8,
-        underlying_amount: U256,
-    ) -> Result<u64, ProgramError> {
-        // the SPL amount would be the same in case no truncating is required.
-        if underlying_decimals == spl_decimals {
-            return Ok(underlying_amount.as_u64());
-        }
-        if underlying_decimals > spl_decimals {
-            let spl_amount =
-                underlying_amount / U256::exp10((underlying_decimals - spl_decimals) as usize);
-            return spl_amount
-                .try_into()
-                .map_err(|_| LockerError::UnexpectedDecimalConversion.into());
-        }
-        Err(LockerError::UnexpectedDecimalConversion.into())
-    }
+    /// Hands the `MintTokens` authority of the canonical mint to the state
+    /// PDA. Pins the mint if none went through yet.
+    fn process_claim_mint_authority(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_account_info = next_account_info(account_info_iter)?;
 
-    fn underlying_amount_from_spl_amount(
-        underlying_decimals: u8,
-        spl_decimals: u8,
+        let state_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+
+        let mut state_info = Self::unpack_state_mut(state_account_info, program_id)?;
+        Self::check_authority(accounts, &state_info, signer_account_info)?;
+
+        let minter_info = next_account_info(account_info_iter)?;
+        let mint = Self::unpack_mint(minter_info)?;
+        if mint.mint_authority != COption::Some(*signer_account_info.key) {
+            return Err(LockerError::InvalidAuthority.into());
+        }
+        // the PDA only ever holds the authority of the canonical mint
+        state_info.pin_mint(minter_info.key)?;
+        Locker::pack(state_info, &mut state_account_info.data.borrow_mut())?;
+
+        let token_program_info = next_account_info(account_info_iter)?;
+        if !(spl_token::id().eq(token_program_info.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let set_authority_ix = spl_token::instruction::set_authority(
+            token_program_info.key,
+            minter_info.key,
+            Some(state_account_info.key),
+            spl_token::instruction::AuthorityType::MintTokens,
+            signer_account_info.key,
+            &[signer_account_info.key]
+        )?;
+
+        invoke(
+            &set_authority_ix,
+            &[
+                minter_info.clone(),
+                signer_account_info.clone(),
+                token_program_info.clone(),
+            ]
+        )?;
+        msg!("mint authority of {} handed to {}", minter_info.key, state_account_info.key);
+        Self::set_result(0, &state_info)?;
+
+        Ok(())
+    }
+
+    /// Burns wrapped tokens stranded in a token account owned by the state
+    /// PDA, so `total_minted` can be brought back in line with the supply.
+    fn process_admin_burn(
+        accounts: &[AccountInfo],
+        amount: u64,
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_account_info = next_account_info(account_info_iter)?;
+
+        let state_account_info = next_account_info(account_info_iter)?;
+        let nonce = Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+
+        let mut state_info = Self::unpack_state_mut(state_account_info, program_id)?;
+        Self::check_authority(accounts, &state_info, signer_account_info)?;
+
+        let token_account_info = next_account_info(account_info_iter)?;
+        if !(token_account_info.owner.eq(&spl_token::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let token_account = spl_token::state::Account::unpack(&token_account_info.data.borrow())?;
+        if !(token_account.owner.eq(state_account_info.key)) {
+            return Err(ProgramError::IllegalOwner);
+        }
+        if token_account.amount < amount {
+            msg!("Token account holds {}, cannot burn {}", token_account.amount, amount);
+            return Err(LockerError::InsufficientTokenBalance.into());
+        }
+
+        let minter_info = next_account_info(account_info_iter)?;
+        if !(minter_info.owner.eq(&spl_token::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let token_program_info = next_account_info(account_info_iter)?;
+        if !(spl_token::id().eq(token_program_info.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        state_info.total_minted = state_info
+            .total_minted
+            .checked_sub(amount)
+            .ok_or(LockerError::InsufficientMinted)?;
+        state_info.update_collateralization_ratio()?;
+        state_info.in_progress = true;
+        Locker::pack(state_info, &mut state_account_info.data.borrow_mut())?;
+
+        let burn_ix = spl_token::instruction::burn(
+            token_program_info.key,
+            token_account_info.key,
+            minter_info.key,
+            state_account_info.key,
+            &[],
+            amount
+        )?;
+
+        invoke_signed(
+            &burn_ix,
+            &[
+                state_account_info.clone(),
+                minter_info.clone(),
+                token_account_info.clone(),
+                token_program_info.clone(),
+            ],
+            &[&[&b"Locker"[..], &b"Init"[..], &[nonce]]],
+        )?;
+        Self::clear_in_progress(state_account_info)?;
+        msg!("admin burn of {} from {} by {}", amount, token_account_info.key, signer_account_info.key);
+        Self::emit_ratio(&state_info)?;
+        Self::set_result(amount, &state_info)?;
+
+        Ok(())
+    }
+
+    /// Creates the release record for a destination ahead of its first
+    /// release, so the release itself does not pay for the account. Anyone
+    /// may pay, and an existing record is left untouched.
+    fn process_create_release_record(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payer_account_info = next_account_info(account_info_iter)?;
+        if !payer_account_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let destination_info = next_account_info(account_info_iter)?;
+        let release_record_info = next_account_info(account_info_iter)?;
+
+        let system_program_info = next_account_info(account_info_iter)?;
+        if !(system_program_info.key.eq(&system_program::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let release_record = Self::load_release_record(
+            release_record_info,
+            destination_info,
+            payer_account_info,
+            system_program_info,
+            program_id
+        )?;
+        ReleaseRecord::pack(release_record, &mut release_record_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Closes the release record of `destination` and pays its rent to the
+    /// signer. The recipient may close their own record at any time, the
+    /// authority only once no release has reached it for
+    /// `RELEASE_RECORD_INACTIVITY_SLOTS`. Replay protection lives in the
+    /// processed bitmap, so the record is bookkeeping only and a later
+    /// release starts a fresh one.
+    fn process_close_recipient_account(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_account_info = next_account_info(account_info_iter)?;
+
+        let state_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+        // refuses while a release is in flight and may still touch the record
+        let state_info = Self::unpack_state(state_account_info, program_id)?;
+
+        let destination_info = next_account_info(account_info_iter)?;
+        let release_record_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(release_record_info, &[b"Locker", b"Released", destination_info.key.as_ref()], program_id)?;
+        Self::check_owner(release_record_info, program_id)?;
+        Self::check_writable(release_record_info)?;
+        let release_record = ReleaseRecord::unpack(&release_record_info.data.borrow())?;
+
+        if !(signer_account_info.is_signer && signer_account_info.key.eq(&release_record.destination)) {
+            Self::check_authority(accounts, &state_info, signer_account_info)?;
+            let idle_slot = release_record.last_release_slot
+                .checked_add(state::RELEASE_RECORD_INACTIVITY_SLOTS)
+                .ok_or(LockerError::ArithmeticOverflow)?;
+            if Clock::get()?.slot < idle_slot {
+                msg!("Release record of {} is active until slot {}", destination_info.key, idle_slot);
+                return Err(LockerError::ReleaseRecordActive.into());
+            }
+        }
+
+        let rent_lamports = release_record_info.lamports();
+        let signer_lamports = signer_account_info.lamports()
+            .checked_add(rent_lamports)
+            .ok_or(LockerError::ArithmeticOverflow)?;
+        **release_record_info.try_borrow_mut_lamports()? = 0;
+        **signer_account_info.try_borrow_mut_lamports()? = signer_lamports;
+        release_record_info.data.borrow_mut().fill(0);
+        msg!("release record of {} closed, {} lamports to {}", destination_info.key, rent_lamports, signer_account_info.key);
+
+        Ok(())
+    }
+
+    /// Queues `change` behind the config timelock, replacing any change
+    /// still pending. Every config-mutating instruction lands here.
+    fn process_queue_config_change(
+        accounts: &[AccountInfo],
+        change: ConfigChange,
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_account_info = next_account_info(account_info_iter)?;
+
+        let state_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+
+        let mut state_info = Self::unpack_state_mut(state_account_info, program_id)?;
+        Self::check_config_authority(accounts, &state_info, signer_account_info, &change)?;
+        let unlock_slot = Clock::get()?.slot
+            .checked_add(state::CONFIG_TIMELOCK_SLOTS)
+            .ok_or(LockerError::ArithmeticOverflow)?;
+        if let Some(pending_change) = state_info.pending_change {
+            msg!("Replacing pending change {:?}", pending_change);
+        }
+        msg!("Queued {:?}, applicable from slot {}", change, unlock_slot);
+        state_info.pending_change = Some(change);
+        state_info.pending_change_slot = unlock_slot;
+        Locker::pack(state_info, &mut state_account_info.data.borrow_mut())?;
+        Self::set_result(0, &state_info)?;
+
+        Ok(())
+    }
+
+    /// Queues the given limits as one change, filling in the ones left out
+    /// from the current config. The combination is validated up front, and
+    /// nothing else can change the limits before it applies since queueing
+    /// any other change replaces it.
+    fn process_update_config(
+        accounts: &[AccountInfo],
+        update: instruction::UpdateConfig,
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+        let current = Self::unpack_state(state_account_info, program_id)?.config_limits();
+
+        let limits = ConfigLimits {
+            epoch_mint_cap: update.epoch_mint_cap.unwrap_or(current.epoch_mint_cap),
+            deposit_cap: update.deposit_cap.unwrap_or(current.deposit_cap),
+            large_release_threshold: update.large_release_threshold.unwrap_or(current.large_release_threshold),
+            max_mint_per_tx: update.max_mint_per_tx.unwrap_or(current.max_mint_per_tx),
+        };
+        if let Err(error) = limits.validate() {
+            msg!("Rejected {:?}", limits);
+            return Err(error);
+        }
+        Self::process_queue_config_change(accounts, ConfigChange::Limits(limits), program_id)
+    }
+
+    fn process_apply_config_change(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_account_info = next_account_info(account_info_iter)?;
+
+        let state_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+
+        let mut state_info = Self::unpack_state_mut(state_account_info, program_id)?;
+        let change = state_info.pending_change.ok_or(LockerError::NoPendingConfigChange)?;
+        Self::check_config_authority(accounts, &state_info, signer_account_info, &change)?;
+        let slot = Clock::get()?.slot;
+        if slot < state_info.pending_change_slot {
+            msg!("{:?} is locked until slot {}, now {}", change, state_info.pending_change_slot, slot);
+            return Err(LockerError::ConfigChangeLocked.into());
+        }
+        state_info.apply_config_change(change);
+        state_info.pending_change = None;
+        state_info.pending_change_slot = 0;
+        Locker::pack(state_info, &mut state_account_info.data.borrow_mut())?;
+        Self::set_result(0, &state_info)?;
+
+        Ok(())
+    }
+
+    fn process_read_log(
+        accounts: &[AccountInfo],
+        direction: LogDirection,
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let log_account_info = next_account_info(account_info_iter)?;
+        let log_seed: &[u8] = match direction {
+            LogDirection::LockAndMint => b"Mint",
+            LogDirection::BurnAndRelease => b"Burn",
+        };
+        Self::verify_pda(log_account_info, &[b"Locker", log_seed], program_id)?;
+        Self::check_owner(log_account_info, program_id)?;
+        Self::check_log_size(log_account_info)?;
+
+        set_return_data(&log_account_info.data.borrow());
+
+        Ok(())
+    }
+
+    /// Grows a log account created under an older, shorter layout to
+    /// `LOGSIZE`, with the signer topping up rent. The entry it held cannot
+    /// be read under the new layout, so it is cleared.
+    fn process_resize_log_account(
+        accounts: &[AccountInfo],
+        direction: LogDirection,
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_account_info = next_account_info(account_info_iter)?;
+
+        let state_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+        let state_info = Self::unpack_state(state_account_info, program_id)?;
+        Self::check_authority(accounts, &state_info, signer_account_info)?;
+
+        let log_account_info = next_account_info(account_info_iter)?;
+        let log_seed: &[u8] = match direction {
+            LogDirection::LockAndMint => b"Mint",
+            LogDirection::BurnAndRelease => b"Burn",
+        };
+        Self::verify_pda(log_account_info, &[b"Locker", log_seed], program_id)?;
+        Self::check_owner(log_account_info, program_id)?;
+        if log_account_info.data_len() >= state::LOGSIZE {
+            return Self::check_log_size(log_account_info);
+        }
+        Self::check_writable(log_account_info)?;
+
+        let system_program_info = next_account_info(account_info_iter)?;
+        if !(system_program_info.key.eq(&system_program::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let top_up = Rent::get()?
+            .minimum_balance(state::LOGSIZE)
+            .saturating_sub(log_account_info.lamports());
+        if top_up > 0 {
+            invoke(
+                &system_instruction::transfer(signer_account_info.key, log_account_info.key, top_up),
+                &[
+                    signer_account_info.clone(),
+                    log_account_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+        }
+        msg!("Resizing log account {} from {} to {} bytes", log_account_info.key, log_account_info.data_len(), state::LOGSIZE);
+        log_account_info.realloc(state::LOGSIZE, true)?;
+
+        let mut data = log_account_info.data.borrow_mut();
+        data.fill(0);
+        match direction {
+            LogDirection::LockAndMint => LockAndMintLog::pack(LockAndMintLog::default(), &mut data),
+            LogDirection::BurnAndRelease => BurnAndReleaseLog::pack(BurnAndReleaseLog::default(), &mut data),
+        }
+    }
+
+    /// Grows a state account created under the `LEGACY_STATESIZE` layout to
+    /// `STATESIZE`. The authority and both totals carry over, everything
+    /// else starts out as `Initialize` leaves it. The signer tops the vault
+    /// up so it still holds rent on top of `total_locked`. An account
+    /// already migrated is left alone.
+    fn process_migrate_state(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_account_info = next_account_info(account_info_iter)?;
+
+        let state_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+        Self::check_owner(state_account_info, program_id)?;
+        if state_account_info.data_len() != state::LEGACY_STATESIZE {
+            return Self::unpack_state(state_account_info, program_id).map(|_| ());
+        }
+        Self::check_writable(state_account_info)?;
+        let state_info = Locker::unpack_legacy(&state_account_info.data.borrow())?;
+        if !state_info.is_initialized() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        Self::check_authority(accounts, &state_info, signer_account_info)?;
+
+        let system_program_info = next_account_info(account_info_iter)?;
+        if !(system_program_info.key.eq(&system_program::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // the vault holds the locked lamports, they cannot count as rent
+        let top_up = Rent::get()?
+            .minimum_balance(state::STATESIZE)
+            .checked_add(state_info.total_locked)
+            .ok_or(LockerError::ArithmeticOverflow)?
+            .saturating_sub(state_account_info.lamports());
+        if top_up > 0 {
+            invoke(
+                &system_instruction::transfer(signer_account_info.key, state_account_info.key, top_up),
+                &[
+                    signer_account_info.clone(),
+                    state_account_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+        }
+        msg!("Migrating state account {} from {} to {} bytes", state_account_info.key, state_account_info.data_len(), state::STATESIZE);
+        state_account_info.realloc(state::STATESIZE, true)?;
+        Locker::pack(state_info, &mut state_account_info.data.borrow_mut())?;
+        Self::set_result(0, &state_info)?;
+
+        Ok(())
+    }
+
+    /// Records `claim` for its recipient to pull later with `ClaimMint`. The
+    /// claim is keyed by its Ethereum event, and both the sequence and the
+    /// event are marked processed here, so the same event can be neither
+    /// recorded twice nor also pushed with `Mint`.
+    fn process_record_claim(
+        accounts: &[AccountInfo],
+        claim: Claim,
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_account_info = next_account_info(account_info_iter)?;
+
+        let state_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+        let state_info = Self::unpack_state(state_account_info, program_id)?;
+        Self::check_authority(accounts, &state_info, signer_account_info)?;
+        state_info.check_mint_amount(claim.amount)?;
+
+        let claim_account_info = next_account_info(account_info_iter)?;
+        let log_index_bytes = claim.eth_log_index.to_le_bytes();
+        let claim_nonce = Self::verify_pda(
+            claim_account_info,
+            &[b"Locker", b"Claim", &claim.eth_tx_hash, &log_index_bytes],
+            program_id
+        )?;
+        Self::check_writable(claim_account_info)?;
+
+        let processed_account_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        if !(system_program_info.key.eq(&system_program::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::mark_processed(
+            processed_account_info,
+            signer_account_info,
+            system_program_info,
+            claim.sequence,
+            program_id
+        )?;
+        let event_marker_info = next_account_info(account_info_iter)?;
+        Self::mark_event_processed(
+            event_marker_info,
+            signer_account_info,
+            system_program_info,
+            &claim.eth_tx_hash,
+            claim.eth_log_index,
+            program_id
+        )?;
+
+        if claim_account_info.data_is_empty() {
+            Self::create_pda_account(
+                claim_account_info,
+                signer_account_info,
+                system_program_info,
+                state::CLAIM_SIZE,
+                &[&b"Locker"[..], &b"Claim"[..], &claim.eth_tx_hash, &log_index_bytes, &[claim_nonce]],
+                program_id,
+            )?;
+        } else {
+            Self::check_owner(claim_account_info, program_id)?;
+        }
+        Claim::pack(claim, &mut claim_account_info.data.borrow_mut())?;
+        msg!("claim {} of {} recorded for {}", claim.sequence, claim.amount, claim.recipient);
+        Self::set_result(claim.amount, &state_info)?;
+
+        Ok(())
+    }
+
+    /// Mints a recorded claim to a token account of its recipient, who must
+    /// sign. The state PDA has to hold the mint authority, since nobody
+    /// else signing here may.
+    fn process_claim_mint(
+        accounts: &[AccountInfo],
+        eth_tx_hash: &[u8; 32],
+        eth_log_index: u64,
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let recipient_info = next_account_info(account_info_iter)?;
+        if !recipient_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let state_account_info = next_account_info(account_info_iter)?;
+        let nonce = Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+
+        let claim_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(
+            claim_account_info,
+            &[b"Locker", b"Claim", eth_tx_hash, &eth_log_index.to_le_bytes()],
+            program_id
+        )?;
+        Self::check_owner(claim_account_info, program_id)?;
+        Self::check_writable(claim_account_info)?;
+        let mut claim = Claim::unpack(&claim_account_info.data.borrow())?;
+        if !(claim.recipient.eq(recipient_info.key)) {
+            return Err(LockerError::InvalidAuthority.into());
+        }
+        if claim.claimed {
+            return Err(LockerError::AlreadyProcessed.into());
+        }
+
+        let token_account_info = next_account_info(account_info_iter)?;
+        let minter_info = next_account_info(account_info_iter)?;
+        if !(token_account_info.owner.eq(&spl_token::id()) && minter_info.owner.eq(&spl_token::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let token_account = spl_token::state::Account::unpack(&token_account_info.data.borrow())?;
+        if !(token_account.owner.eq(recipient_info.key) && token_account.mint.eq(minter_info.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mint = Self::unpack_mint(minter_info)?;
+        if mint.mint_authority != COption::Some(*state_account_info.key) {
+            return Err(LockerError::InvalidAuthority.into());
+        }
+
+        let token_program_info = next_account_info(account_info_iter)?;
+        if !(spl_token::id().eq(token_program_info.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let mut state_info = Self::unpack_state_mut(state_account_info, program_id)?;
+        state_info.total_minted = state_info.total_minted
+            .checked_add(claim.amount)
+            .ok_or(LockerError::ArithmeticOverflow)?;
+        state_info.record_epoch_mint(Clock::get()?.epoch, claim.amount)?;
+        // lamports are locked, the mint's own decimals are minted
+        Self::assert_solvent(
+            state_info.total_locked,
+            state_info.total_minted,
+            spl_token::native_mint::DECIMALS,
+            mint.decimals
+        )?;
+        state_info.mint_decimals = mint.decimals;
+        state_info.update_collateralization_ratio()?;
+        state_info.in_progress = true;
+        Locker::pack(state_info, &mut state_account_info.data.borrow_mut())?;
+
+        claim.claimed = true;
+        Claim::pack(claim, &mut claim_account_info.data.borrow_mut())?;
+
+        let mint_ix = spl_token::instruction::mint_to(
+            token_program_info.key,
+            minter_info.key,
+            token_account_info.key,
+            state_account_info.key,
+            &[],
+            claim.amount
+        )?;
+        invoke_signed(
+            &mint_ix,
+            &[
+                state_account_info.clone(),
+                minter_info.clone(),
+                token_account_info.clone(),
+                token_program_info.clone(),
+            ],
+            &[&[&b"Locker"[..], &b"Init"[..], &[nonce]]],
+        ).map_err(|error| Self::map_token_error("mint_to", error))?;
+        Self::clear_in_progress(state_account_info)?;
+        Self::emit_ratio(&state_info)?;
+        Self::set_result(claim.amount, &state_info)?;
+
+        Ok(())
+    }
+
+    /// Loads the release record for `destination_info`, creating the PDA with
+    /// `payer_account_info` covering rent the first time it is used.
+    fn load_release_record(
+        release_record_info: &AccountInfo,
+        destination_info: &AccountInfo,
+        payer_account_info: &AccountInfo,
+        system_program_info: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<ReleaseRecord, ProgramError> {
+        let release_record_nonce = Self::verify_pda(
+            release_record_info,
+            &[b"Locker", b"Released", destination_info.key.as_ref()],
+            program_id
+        )?;
+        Self::check_writable(release_record_info)?;
+        if release_record_info.data_is_empty() {
+            msg!("Creating release record for {}", destination_info.key);
+            Self::create_pda_account(
+                release_record_info,
+                payer_account_info,
+                system_program_info,
+                state::RELEASE_RECORD_SIZE,
+                &[&b"Locker"[..], &b"Released"[..], destination_info.key.as_ref(), &[release_record_nonce]],
+                program_id,
+            )?;
+        } else if !(release_record_info.owner.eq(program_id)) {
+            return Err(LockerError::InvalidAccountOwner.into());
+        }
+
+        let mut release_record = ReleaseRecord::unpack_unchecked(&release_record_info.data.borrow())?;
+        if !release_record.is_initialized() {
+            release_record.is_initialized = true;
+            release_record.destination = *destination_info.key;
+        }
+        Ok(release_record)
+    }
+
+    /// Returns the amount locked but not yet minted, in underlying units, as
+    /// a 32 byte big-endian U256 through return data.
+    fn process_get_in_flight(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let state_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+
+        let state_info = Self::unpack_state(state_account_info, program_id)?;
+        let mut data = [0u8; 32];
+        Self::in_flight(&state_info)?.to_big_endian(&mut data);
+        set_return_data(&data);
+
+        Ok(())
+    }
+
+    /// Returns a packed `HealthSummary` through return data. Unlike the
+    /// other instructions it reads the state even while `in_progress` is
+    /// set, and reports the flag.
+    fn process_get_health(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let state_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+        if state_account_info.data_len() != state::STATESIZE {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let state_info = Locker::unpack(&state_account_info.data.borrow())?;
+        let mut data = [0u8; state::HEALTH_SUMMARY_SIZE];
+        HealthSummary::pack(HealthSummary {
+            total_locked: state_info.total_locked,
+            total_minted: state_info.total_minted,
+            collateralization_ratio: state_info.collateralization_ratio,
+            in_flight: Self::in_flight(&state_info)?,
+            vault_balance: state_account_info.lamports(),
+            in_progress: state_info.in_progress,
+        }, &mut data)?;
+        set_return_data(&data);
+
+        Ok(())
+    }
+
+    /// Returns the registered Ethereum contract through return data.
+    fn process_get_eth_contract(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let state_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+
+        let state_info = Self::unpack_state(state_account_info, program_id)?;
+        set_return_data(&state_info.eth_contract);
+
+        Ok(())
+    }
+
+    /// Returns the wrapped mint's metadata account through return data.
+    fn process_get_metadata(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let state_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+
+        let state_info = Self::unpack_state(state_account_info, program_id)?;
+        set_return_data(state_info.metadata.as_ref());
+
+        Ok(())
+    }
+
+    /// Returns the state account's bytes as stored, without decoding them, so
+    /// clients can check their own decoder against the canonical layout.
+    fn process_dump_state(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let state_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+        Self::check_owner(state_account_info, program_id)?;
+        if state_account_info.data_len() != state::STATESIZE {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        set_return_data(&state_account_info.data.borrow());
+
+        Ok(())
+    }
+
+    /// Returns a packed `RolesView` through return data.
+    fn process_get_roles(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let state_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+
+        let state_info = Self::unpack_state(state_account_info, program_id)?;
+        let mut data = [0u8; state::ROLES_VIEW_SIZE];
+        RolesView::pack(RolesView {
+            authority: state_info.authority,
+            backup_authority: state_info.backup_authority,
+        }, &mut data)?;
+        set_return_data(&data);
+
+        Ok(())
+    }
+
+    /// Amount locked but not yet minted, in underlying units. Lamports are
+    /// locked and units of the wrapped mint are minted, so the totals are
+    /// scaled like `assert_solvent` scales them before subtracting.
+    fn in_flight(state_info: &Locker) -> Result<U256, ProgramError> {
+        let locked_decimals = spl_token::native_mint::DECIMALS;
+        let (locked, minted) = state::scale_totals(
+            state_info.total_locked,
+            locked_decimals,
+            state_info.total_minted,
+            state_info.mint_decimals
+        )?;
+        let in_flight = locked
+            .checked_sub(minted)
+            .ok_or(LockerError::ArithmeticOverflow)?;
+
+        let decimals = locked_decimals.max(state_info.mint_decimals);
+        Self::validate_decimals(state_info.underlying_decimals, decimals)?;
+        if state_info.underlying_decimals >= decimals {
+            in_flight
+                .checked_mul(U256::exp10((state_info.underlying_decimals - decimals) as usize))
+                .ok_or_else(|| LockerError::ArithmeticOverflow.into())
+        } else {
+            Ok(in_flight / U256::exp10((decimals - state_info.underlying_decimals) as usize))
+        }
+    }
+
+    /// Runs the checks a release of `amount` to `destination` would make,
+    /// without moving funds, and returns the outcome as a little-endian u64
+    /// through return data: 0 on success, otherwise the error code the
+    /// release would fail with.
+    fn process_preview_release(
+        accounts: &[AccountInfo],
+        amount: u64,
+        destination: &Pubkey,
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let status = match Self::check_release(accounts, amount, destination, program_id) {
+            Ok(()) => 0u64,
+            Err(error) => {
+                msg!("Release would fail: {}", error);
+                u64::from(error)
+            }
+        };
+        set_return_data(&status.to_le_bytes());
+
+        Ok(())
+    }
+
+    fn check_release(
+        accounts: &[AccountInfo],
+        amount: u64,
+        destination: &Pubkey,
+        program_id: &Pubkey
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let state_account_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(state_account_info, &[b"Locker", b"Init"], program_id)?;
+        if destination.eq(state_account_info.key) {
+            return Err(LockerError::InvalidDestination.into());
+        }
+        let state_info = Self::unpack_state(state_account_info, program_id)?;
+        if state_info.total_locked < amount {
+            return Err(LockerError::InsufficientLocked.into());
+        }
+        if Self::releasable_lamports(state_account_info)? < amount {
+            return Err(LockerError::InsufficientLiquidity.into());
+        }
+
+        let release_record_info = next_account_info(account_info_iter)?;
+        Self::verify_pda(release_record_info, &[b"Locker", b"Released", destination.as_ref()], program_id)?;
+        if !release_record_info.data_is_empty() {
+            Self::check_owner(release_record_info, program_id)?;
+            ReleaseRecord::unpack_unchecked(&release_record_info.data.borrow())?
+                .total_released
+                .checked_add(amount)
+                .ok_or(LockerError::ArithmeticOverflow)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets bit `sequence` in the processed-event bitmap, creating the
+    /// bitmap account for its window with `payer_account_info` covering rent.
+    /// Windows are `[b"Locker", b"Processed", window]` PDAs of
+    /// `BITS_PER_ACCOUNT` sequences each. The sequence is assigned by the
+    /// Ethereum contract and shared by every inbound event, so mints and
+    /// releases use the same bitmaps.
+    fn mark_processed(
+        processed_account_info: &AccountInfo,
+        payer_account_info: &AccountInfo,
+        system_program_info: &AccountInfo,
+        sequence: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let window_bytes = state::bitmap_window(sequence).to_le_bytes();
+        let nonce = Self::verify_pda(
+            processed_account_info,
+            &[b"Locker", b"Processed", &window_bytes],
+            program_id
+        )?;
+        Self::check_writable(processed_account_info)?;
+
+        if processed_account_info.data_is_empty() {
+            Self::create_pda_account(
+                processed_account_info,
+                payer_account_info,
+                system_program_info,
+                state::BITMAP_SIZE,
+                &[&b"Locker"[..], &b"Processed"[..], &window_bytes, &[nonce]],
+                program_id,
+            )?;
+        } else {
+            Self::check_owner(processed_account_info, program_id)?;
+        }
+
+        let bit = sequence % state::BITS_PER_ACCOUNT;
+        let mut bitmap = processed_account_info.data.borrow_mut();
+        if state::is_set(&bitmap, bit) {
+            return Err(LockerError::AlreadyProcessed.into());
+        }
+        state::set_bit(&mut bitmap, bit)
+    }
+
+    /// Creates the empty marker PDA for an Ethereum event, derived from
+    /// `[b"Locker", b"Event", eth_tx_hash, eth_log_index]`. Unlike the
+    /// sequence, the event's position on Ethereum cannot be picked by a
+    /// relayer, so two relayers minting the same event under different
+    /// sequences still collide here.
+    fn mark_event_processed(
+        event_marker_info: &AccountInfo,
+        payer_account_info: &AccountInfo,
+        system_program_info: &AccountInfo,
+        eth_tx_hash: &[u8; 32],
+        eth_log_index: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let log_index_bytes = eth_log_index.to_le_bytes();
+        let nonce = Self::verify_pda(
+            event_marker_info,
+            &[b"Locker", b"Event", eth_tx_hash, &log_index_bytes],
+            program_id
+        )?;
+        Self::check_writable(event_marker_info)?;
+        // the marker holds no data, owning it is what records the event
+        if event_marker_info.owner.eq(program_id) {
+            return Err(LockerError::AlreadyProcessed.into());
+        }
+
+        Self::create_pda_account(
+            event_marker_info,
+            payer_account_info,
+            system_program_info,
+            0,
+            &[&b"Locker"[..], &b"Event"[..], &eth_tx_hash[..], &log_index_bytes, &[nonce]],
+            program_id,
+        )
+    }
+
+    /// Creates the PDA `target_info` with `space` bytes owned by this
+    /// program. `create_account` refuses any address that already holds
+    /// lamports, so anyone could block a PDA by sending it dust first.
+    /// A pre-funded account is topped up to rent exemption instead and then
+    /// allocated and assigned under the PDA seeds.
+    fn create_pda_account(
+        target_info: &AccountInfo,
+        payer_account_info: &AccountInfo,
+        system_program_info: &AccountInfo,
+        space: usize,
+        seeds: &[&[u8]],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let required_balance = Rent::get()?.minimum_balance(space);
+        let accounts = [
+            payer_account_info.clone(),
+            target_info.clone(),
+            system_program_info.clone(),
+        ];
+        if target_info.lamports() == 0 {
+            let create_account_ix = system_instruction::create_account(
+                payer_account_info.key,
+                target_info.key,
+                required_balance,
+                space as u64,
+                program_id
+            );
+            return invoke_signed(&create_account_ix, &accounts, &[seeds]);
+        }
+
+        let top_up = required_balance.saturating_sub(target_info.lamports());
+        if top_up > 0 {
+            invoke(
+                &system_instruction::transfer(payer_account_info.key, target_info.key, top_up),
+                &accounts,
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(target_info.key, space as u64),
+            &accounts[1..],
+            &[seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(target_info.key, program_id),
+            &accounts[1..],
+            &[seeds],
+        )
+    }
+
+    /// Moves `amount` lamports out of the vault by debiting it directly.
+    ///
+    /// The vault is the program-owned state PDA, which carries data, so the
+    /// system program would refuse to transfer out of it. Any other owner
+    /// means the caller passed the wrong account.
+    fn transfer_from_vault(
+        vault_info: &AccountInfo,
+        destination_info: &AccountInfo,
+        amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        Self::check_writable(destination_info)?;
+        if !vault_info.owner.eq(program_id) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        // the last line of defense, whatever the counters say
+        if amount > Self::releasable_lamports(vault_info)? {
+            msg!("Vault holds {} lamports, cannot release {}", vault_info.lamports(), amount);
+            return Err(LockerError::InsufficientLiquidity.into());
+        }
+        let vault_lamports = vault_info.lamports()
+            .checked_sub(amount)
+            .ok_or(LockerError::InsufficientLiquidity)?;
+        let destination_lamports = destination_info.lamports()
+            .checked_add(amount)
+            .ok_or(LockerError::ArithmeticOverflow)?;
+        **vault_info.try_borrow_mut_lamports()? = vault_lamports;
+        **destination_info.try_borrow_mut_lamports()? = destination_lamports;
+        Ok(())
+    }
+
+    /// Turns the token program errors a user can act on into bridge errors,
+    /// logging which CPI failed. Anything else is passed through as is.
+    fn map_token_error(cpi: &str, error: ProgramError) -> ProgramError {
+        let mapped = match error {
+            ProgramError::Custom(code) if code == TokenError::InsufficientFunds as u32 => {
+                LockerError::InsufficientTokenBalance
+            }
+            ProgramError::Custom(code) if code == TokenError::OwnerMismatch as u32 => {
+                LockerError::TokenOwnerMismatch
+            }
+            ProgramError::Custom(code) if code == TokenError::AccountFrozen as u32 => {
+                LockerError::TokenAccountFrozen
+            }
+            _ => {
+                msg!("{} CPI failed: {}", cpi, error);
+                return error;
+            }
+        };
+        msg!("{} CPI failed: {}", cpi, mapped);
+        mapped.into()
+    }
+
+    /// Lamports the vault can pay out without dipping into its rent reserve.
+    fn releasable_lamports(vault_info: &AccountInfo) -> Result<u64, ProgramError> {
+        let rent_minimum = Rent::get()?.minimum_balance(vault_info.data_len());
+        Ok(vault_info.lamports().saturating_sub(rent_minimum))
+    }
+
+    /// Reports the outcome of a state-mutating instruction through return data.
+    fn set_result(amount: u64, state_info: &Locker) -> ProgramResult {
+        Self::set_result_with(amount, state_info, &[])
+    }
+
+    /// `set_result` with `payload` appended after the `InstructionResult`.
+    fn set_result_with(amount: u64, state_info: &Locker, payload: &[u8]) -> ProgramResult {
+        let mut data = vec![0u8; state::INSTRUCTION_RESULT_SIZE + payload.len()];
+        InstructionResult::pack(InstructionResult {
+            success: true,
+            amount,
+            total_locked: state_info.total_locked,
+            total_minted: state_info.total_minted,
+        }, &mut data[..state::INSTRUCTION_RESULT_SIZE])?;
+        data[state::INSTRUCTION_RESULT_SIZE..].copy_from_slice(payload);
+        set_return_data(&data);
+        Ok(())
+    }
+
+    /// Emits `data` with `sol_log_data`, behind the program's event tag and
+    /// the Ethereum contract it is meant for
+    fn emit_event(state_info: &Locker, data: &[u8]) {
+        sol_log_data(&[&wire::EVENT_TAG, &state_info.eth_contract, data]);
+    }
+
+    /// Emits the collateralization ratio after the totals moved, behind its
+    /// own tag so relayers skip it and monitors need not decode anything else.
+    /// The ratio is recomputed from the totals rather than read from the
+    /// cache, with the same scaling `assert_solvent` uses.
+    fn emit_ratio(state_info: &Locker) -> ProgramResult {
+        let ratio = state_info.compute_collateralization_ratio()?;
+        sol_log_data(&[&wire::RATIO_TAG, &state_info.eth_contract, &ratio.to_le_bytes()]);
+        Ok(())
+    }
+
+    fn unpack_state(state_account_info: &AccountInfo, program_id: &Pubkey) -> Result<Locker, ProgramError> {
+        Self::check_owner(state_account_info, program_id)?;
+        if state_account_info.data_len() != state::STATESIZE {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let state_info = Locker::unpack_unchecked(&state_account_info.data.borrow())?;
+        if !state_info.is_initialized() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        // set while a state-mutating instruction is waiting on a CPI
+        if state_info.in_progress {
+            return Err(LockerError::Reentrancy.into());
+        }
+        Ok(state_info)
+    }
+
+    /// `unpack_state` for instructions that write the state back.
+    fn unpack_state_mut(state_account_info: &AccountInfo, program_id: &Pubkey) -> Result<Locker, ProgramError> {
+        Self::check_writable(state_account_info)?;
+        Self::unpack_state(state_account_info, program_id)
+    }
+
+    /// Fails unless the account belongs to this program.
+    fn check_owner(account_info: &AccountInfo, program_id: &Pubkey) -> ProgramResult {
+        if !(account_info.owner.eq(program_id)) {
+            msg!("Account {} is owned by {}", account_info.key, account_info.owner);
+            return Err(LockerError::InvalidAccountOwner.into());
+        }
+        Ok(())
+    }
+
+    /// Fails unless the account was passed writable. The runtime would
+    /// reject the write at the end anyway, this names the account and fails
+    /// before any CPI goes out.
+    fn check_writable(account_info: &AccountInfo) -> ProgramResult {
+        if !account_info.is_writable {
+            msg!("Account {} must be writable", account_info.key);
+            return Err(LockerError::AccountNotWritable.into());
+        }
+        Ok(())
+    }
+
+    fn clear_in_progress(state_account_info: &AccountInfo) -> ProgramResult {
+        let mut state_info = Locker::unpack_unchecked(&state_account_info.data.borrow())?;
+        state_info.in_progress = false;
+        Locker::pack(state_info, &mut state_account_info.data.borrow_mut())
+    }
+
+    /// Accepts either the primary or, when one is set, the backup authority.
+    fn check_authority(
+        accounts: &[AccountInfo],
+        state_info: &Locker,
+        signer_account_info: &AccountInfo,
+    ) -> ProgramResult {
+        if state_info.backup_authority != Pubkey::default()
+            && state_info.backup_authority.eq(signer_account_info.key) {
+            return Self::require_signers(accounts, &[&state_info.backup_authority]);
+        }
+        Self::check_primary_authority(accounts, state_info, signer_account_info)
+    }
+
+    /// Leaf of a release root, one per Ethereum burn.
+    fn release_leaf(recipient: &Pubkey, amount: u64, burn_id: u64) -> [u8; 32] {
+        keccak::hashv(&[recipient.as_ref(), &amount.to_le_bytes(), &burn_id.to_le_bytes()]).to_bytes()
+    }
+
+    /// Pairs are hashed smallest first, so proofs need no left/right flags.
+    fn merkle_parent(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+        if a <= b {
+            keccak::hashv(&[a, b]).to_bytes()
+        } else {
+            keccak::hashv(&[b, a]).to_bytes()
+        }
+    }
+
+    fn verify_release_proof(root: &[u8; 32], leaf: [u8; 32], proof: &[[u8; 32]]) -> bool {
+        let computed = proof
+            .iter()
+            .fold(leaf, |node, sibling| Self::merkle_parent(&node, sibling));
+        computed == *root
+    }
+
+    /// Releases above the threshold need both the authority and the backup
+    /// authority to sign.
+    fn check_large_release(
+        accounts: &[AccountInfo],
+        state_info: &Locker,
+        amount: u64,
+    ) -> ProgramResult {
+        if state_info.large_release_threshold != 0 && amount > state_info.large_release_threshold {
+            // without a backup there is no second key to ask for
+            if state_info.backup_authority == Pubkey::default()
+                || Self::require_signers(accounts, &[&state_info.authority, &state_info.backup_authority]).is_err() {
+                msg!("Release of {} is above the threshold of {}", amount, state_info.large_release_threshold);
+                return Err(LockerError::InsufficientSigners.into());
+            }
+        }
+        Ok(())
+    }
+
+    fn check_primary_authority(
+        accounts: &[AccountInfo],
+        state_info: &Locker,
+        signer_account_info: &AccountInfo,
+    ) -> ProgramResult {
+        // a zeroed authority can only come from corrupt state, never trust it
+        if state_info.authority == Pubkey::default() {
+            return Err(LockerError::InvalidAuthority.into());
+        }
+        if !(state_info.authority.eq(signer_account_info.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::require_signers(accounts, &[&state_info.authority])
+    }
+
+    /// Only the primary authority may change the backup authority, the
+    /// backup may queue and apply everything else.
+    fn check_config_authority(
+        accounts: &[AccountInfo],
+        state_info: &Locker,
+        signer_account_info: &AccountInfo,
+        change: &ConfigChange,
+    ) -> ProgramResult {
+        match change {
+            ConfigChange::BackupAuthority(_) => Self::check_primary_authority(accounts, state_info, signer_account_info),
+            _ => Self::check_authority(accounts, state_info, signer_account_info),
+        }
+    }
+
+    /// Log accounts created before the layout last grew are too small to
+    /// hold an entry and need a resize, anything else must match exactly.
+    fn check_log_size(log_account_info: &AccountInfo) -> ProgramResult {
+        if log_account_info.data_len() < state::LOGSIZE {
+            msg!(
+                "Log account {} holds {} bytes, {} needed, resize it",
+                log_account_info.key,
+                log_account_info.data_len(),
+                state::LOGSIZE
+            );
+            return Err(LockerError::LogAccountTooSmall.into());
+        }
+        if log_account_info.data_len() != state::LOGSIZE {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(())
+    }
+
+    /// Unpacks an SPL mint, rejecting accounts the token program does not
+    /// own and mints that were allocated but never initialized.
+    fn unpack_mint(minter_info: &AccountInfo) -> Result<spl_token::state::Mint, ProgramError> {
+        if !(minter_info.owner.eq(&spl_token::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mint = spl_token::state::Mint::unpack_unchecked(&minter_info.data.borrow())
+            .map_err(|_| LockerError::InvalidMint)?;
+        if !mint.is_initialized {
+            return Err(LockerError::InvalidMint.into());
+        }
+        Ok(mint)
+    }
+
+    /// Catches a client built against a deployment whose mint has other
+    /// decimals, compared against the decimals stored in the locker. `None`
+    /// skips the check.
+    fn check_expected_decimals(
+        expected_spl_decimals: Option<u8>,
+        state_info: &Locker
+    ) -> ProgramResult {
+        match expected_spl_decimals {
+            Some(decimals) if decimals != state_info.mint_decimals => {
+                msg!("Locker stores {} decimals, client expected {}", state_info.mint_decimals, decimals);
+                Err(LockerError::InvalidDecimals.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks `account_info` is the PDA derived from `seeds` and returns its
+    /// bump seed.
+    fn verify_pda(
+        account_info: &AccountInfo,
+        seeds: &[&[u8]],
+        program_id: &Pubkey,
+    ) -> Result<u8, ProgramError> {
+        let (pubkey, bump) = Pubkey::find_program_address(seeds, program_id);
+        if !(account_info.key.eq(&pubkey)) {
+            return Err(LockerError::InvalidPda.into());
+        }
+        Ok(bump)
+    }
+
+    fn require_signers(accounts: &[AccountInfo], required: &[&Pubkey]) -> ProgramResult {
+        for key in required {
+            let signed = accounts
+                .iter()
+                .any(|account_info| account_info.key.eq(*key) && account_info.is_signer);
+            if !signed {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_decimals(underlying_decimals: u8, spl_decimals: u8) -> ProgramResult {
+        // 10^38 is the largest power of ten that still leaves U256 headroom for a u64 amount
+        if underlying_decimals > MAX_DECIMALS || spl_decimals > MAX_DECIMALS {
+            return Err(LockerError::InvalidDecimals.into());
+        }
+        Ok(())
+    }
+
+    /// Fails unless `total_minted` is backed by `total_locked`, once both
+    /// are scaled with `state::scale_totals`.
+    fn assert_solvent(
+        total_locked: u64,
+        total_minted: u64,
+        underlying_decimals: u8,
+        spl_decimals: u8,
+    ) -> ProgramResult {
+        let (locked, minted) = state::scale_totals(total_locked, underlying_decimals, total_minted, spl_decimals)?;
+        if minted > locked {
+            msg!("Minted {} is not backed by locked {}", minted, locked);
+            return Err(LockerError::InsolventMint.into());
+        }
+        Ok(())
+    }
+
+    /// Truncates to SPL units. The dropped remainder stays in the vault, so
+    /// the conversion never mints more than was locked.
+    fn spl_amount_from_underlying_amount(
+        underlying_decimals: u8,
+        spl_decimals: u8,
+        underlying_amount: U256,
+    ) -> Result<u64, ProgramError> {
+        Self::validate_decimals(underlying_decimals, spl_decimals)?;
+        // the SPL amount would be the same in case no truncating is required,
+        // it only has to fit in a u64.
+        if underlying_decimals == spl_decimals {
+            if underlying_amount.bits() > 64 {
+                return Err(LockerError::UnexpectedDecimalConversion.into());
+            }
+            let spl_amount = underlying_amount.low_u64();
+            debug_assert_eq!(U256::from(spl_amount), underlying_amount);
+            return Ok(spl_amount);
+        }
+        if underlying_decimals > spl_decimals {
+            let spl_amount =
+                underlying_amount / U256::exp10((underlying_decimals - spl_decimals) as usize);
+            return spl_amount
+                .try_into()
+                .map_err(|_| LockerError::UnexpectedDecimalConversion.into());
+        }
+        Err(LockerError::UnexpectedDecimalConversion.into())
+    }
+
+    /// Expands to underlying units. An underlying token with fewer decimals
+    /// than the SPL side truncates instead, so a small enough amount
+    /// converts to zero.
+    fn underlying_amount_from_spl_amount(
+        underlying_decimals: u8,
+        spl_decimals: u8,
         spl_amount: u64,
     ) -> Result<U256, ProgramError> {
+        Self::validate_decimals(underlying_decimals, spl_decimals)?;
         // the underlying amount would be the same in case no expansion is required.
         if underlying_decimals == spl_decimals {
             return Ok(U256::from(spl_amount));
         }
         if underlying_decimals > spl_decimals {
+            return U256::from(spl_amount)
+                .checked_mul(U256::exp10((underlying_decimals - spl_decimals) as usize))
+                .ok_or_else(|| LockerError::ArithmeticOverflow.into());
+        }
+        Ok(U256::from(spl_amount) / U256::exp10((spl_decimals - underlying_decimals) as usize))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::{
+        clock::Epoch,
+        entrypoint::{MAX_PERMITTED_DATA_INCREASE, SUCCESS},
+        instruction::Instruction,
+        program_stubs,
+    };
+    use arrayref::array_ref;
+    use std::{cell::RefCell, sync::Once};
+
+    thread_local! {
+        static RETURN_DATA: RefCell<Option<Vec<u8>>> = RefCell::new(None);
+        static CLOCK: RefCell<Clock> = RefCell::new(Clock::default());
+        static INVOKED: RefCell<Vec<Instruction>> = RefCell::new(vec![]);
+        static LOGGED: RefCell<Vec<Vec<Vec<u8>>>> = RefCell::new(vec![]);
+        static CPI_ERROR: RefCell<Option<ProgramError>> = RefCell::new(None);
+    }
+
+    struct TestSyscallStubs;
+
+    impl program_stubs::SyscallStubs for TestSyscallStubs {
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            _signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            INVOKED.with(|invoked| invoked.borrow_mut().push(instruction.clone()));
+            if let Some(error) = CPI_ERROR.with(|cpi_error| cpi_error.borrow_mut().take()) {
+                return Err(error);
+            }
+            // only system transfers move lamports, every other CPI is a no-op
+            if instruction.program_id != system_program::id() || instruction.data.len() != 12 {
+                return Ok(());
+            }
+            if instruction.data[..4] != 2u32.to_le_bytes() {
+                return Ok(());
+            }
+            let amount = u64::from_le_bytes(*array_ref![instruction.data, 4, 8]);
+            let find = |key: &Pubkey| {
+                account_infos
+                    .iter()
+                    .find(|account_info| account_info.key.eq(key))
+                    .ok_or(ProgramError::NotEnoughAccountKeys)
+            };
+            let from = find(&instruction.accounts[0].pubkey)?;
+            let to = find(&instruction.accounts[1].pubkey)?;
+            let from_lamports = from.lamports().checked_sub(amount).ok_or(ProgramError::InsufficientFunds)?;
+            **from.try_borrow_mut_lamports()? = from_lamports;
+            **to.try_borrow_mut_lamports()? += amount;
+            Ok(())
+        }
+
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe {
+                *(var_addr as *mut Clock) = CLOCK.with(|clock| clock.borrow().clone());
+            }
+            SUCCESS
+        }
+
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe {
+                *(var_addr as *mut Rent) = Rent::default();
+            }
+            SUCCESS
+        }
+
+        fn sol_log_data(&self, fields: &[&[u8]]) {
+            let fields = fields.iter().map(|field| field.to_vec()).collect();
+            LOGGED.with(|logged| logged.borrow_mut().push(fields));
+        }
+
+        fn sol_set_return_data(&self, data: &[u8]) {
+            RETURN_DATA.with(|return_data| *return_data.borrow_mut() = Some(data.to_vec()));
+        }
+    }
+
+    fn setup_syscall_stubs() {
+        static ONCE: Once = Once::new();
+        ONCE.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(TestSyscallStubs));
+        });
+    }
+
+    fn take_invoked() -> Vec<Instruction> {
+        INVOKED.with(|invoked| invoked.borrow_mut().drain(..).collect())
+    }
+
+    /// Makes the next CPI fail with `error`, as the callee would.
+    fn fail_next_cpi(error: ProgramError) {
+        CPI_ERROR.with(|cpi_error| *cpi_error.borrow_mut() = Some(error));
+    }
+
+    fn take_logged() -> Vec<Vec<Vec<u8>>> {
+        LOGGED.with(|logged| logged.borrow_mut().drain(..).collect())
+    }
+
+    fn take_return_data() -> Option<Vec<u8>> {
+        RETURN_DATA.with(|return_data| return_data.borrow_mut().take())
+    }
+
+    struct TestAccount {
+        key: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+        owner: Pubkey,
+        is_signer: bool,
+        is_writable: bool,
+    }
+
+    impl TestAccount {
+        fn new(key: Pubkey, lamports: u64, data: Vec<u8>, owner: Pubkey) -> Self {
+            TestAccount {
+                key,
+                lamports,
+                data,
+                owner,
+                is_signer: false,
+                is_writable: true,
+            }
+        }
+
+        fn signer(key: Pubkey) -> Self {
+            TestAccount {
+                is_signer: true,
+                ..TestAccount::new(key, 0, vec![], system_program::id())
+            }
+        }
+
+        fn with_lamports(mut self, lamports: u64) -> Self {
+            self.lamports = lamports;
+            self
+        }
+
+        fn program(key: Pubkey) -> Self {
+            TestAccount::new(key, 0, vec![], Pubkey::default())
+        }
+
+        fn info(&mut self) -> AccountInfo {
+            AccountInfo::new(
+                &self.key,
+                self.is_signer,
+                self.is_writable,
+                &mut self.lamports,
+                &mut self.data,
+                &self.owner,
+                false,
+                Epoch::default(),
+            )
+        }
+    }
+
+    /// Runs the instruction and, like the runtime, discards every account
+    /// change if it fails. Account data is laid out as the runtime does,
+    /// behind its length and with room to grow, so `realloc` works.
+    fn process(program_id: &Pubkey, accounts: &mut [TestAccount], data: &[u8]) -> ProgramResult {
+        let mut buffers: Vec<Vec<u8>> = accounts
+            .iter()
+            .map(|account| {
+                let len = account.data.len();
+                let mut buffer = vec![0u8; 8 + len + MAX_PERMITTED_DATA_INCREASE];
+                buffer[..8].copy_from_slice(&(len as u64).to_le_bytes());
+                buffer[8..8 + len].copy_from_slice(&account.data);
+                buffer
+            })
+            .collect();
+        let snapshot: Vec<u64> = accounts.iter().map(|account| account.lamports).collect();
+        let (result, lens) = {
+            let infos: Vec<AccountInfo> = accounts
+                .iter_mut()
+                .zip(buffers.iter_mut())
+                .map(|(account, buffer)| {
+                    let len = account.data.len();
+                    AccountInfo::new(
+                        &account.key,
+                        account.is_signer,
+                        account.is_writable,
+                        &mut account.lamports,
+                        &mut buffer[8..8 + len],
+                        &account.owner,
+                        false,
+                        Epoch::default(),
+                    )
+                })
+                .collect();
+            let result = Processor::process(program_id, &infos, data);
+            let lens: Vec<usize> = infos.iter().map(AccountInfo::data_len).collect();
+            (result, lens)
+        };
+        if result.is_err() {
+            for (account, lamports) in accounts.iter_mut().zip(snapshot) {
+                account.lamports = lamports;
+            }
+        } else {
+            for ((account, buffer), len) in accounts.iter_mut().zip(buffers).zip(lens) {
+                account.data = buffer[8..8 + len].to_vec();
+            }
+        }
+        result
+    }
+
+    /// Rent the state PDA keeps back from releases.
+    fn vault_reserve() -> u64 {
+        Rent::default().minimum_balance(state::STATESIZE)
+    }
+
+    fn state_account(program_id: &Pubkey, locker: Locker) -> TestAccount {
+        let (key, _) = Pubkey::find_program_address(&[b"Locker", b"Init"], program_id);
+        let mut data = vec![0u8; state::STATESIZE];
+        Locker::pack(locker, &mut data).unwrap();
+        TestAccount::new(key, 0, data, *program_id)
+    }
+
+    fn mintlog_account(program_id: &Pubkey) -> TestAccount {
+        let (key, _) = Pubkey::find_program_address(&[b"Locker", b"Mint"], program_id);
+        let mut data = vec![0u8; state::LOGSIZE];
+        LockAndMintLog::pack(LockAndMintLog::default(), &mut data).unwrap();
+        TestAccount::new(key, 0, data, *program_id)
+    }
+
+    fn burnlog_account(program_id: &Pubkey) -> TestAccount {
+        let (key, _) = Pubkey::find_program_address(&[b"Locker", b"Burn"], program_id);
+        let mut data = vec![0u8; state::LOGSIZE];
+        BurnAndReleaseLog::pack(BurnAndReleaseLog::default(), &mut data).unwrap();
+        TestAccount::new(key, 0, data, *program_id)
+    }
+
+    fn release_record_account(program_id: &Pubkey, destination: &Pubkey) -> TestAccount {
+        let (key, _) = Pubkey::find_program_address(&[b"Locker", b"Released", destination.as_ref()], program_id);
+        TestAccount::new(key, 0, vec![0u8; state::RELEASE_RECORD_SIZE], *program_id)
+    }
+
+    fn mint_account(key: Pubkey, mint_authority: &Pubkey) -> TestAccount {
+        mint_account_with_decimals(key, mint_authority, 9)
+    }
+
+    fn mint_account_with_decimals(key: Pubkey, mint_authority: &Pubkey, decimals: u8) -> TestAccount {
+        let mut data = vec![0u8; spl_token::state::Mint::LEN];
+        spl_token::state::Mint::pack(
+            spl_token::state::Mint {
+                mint_authority: COption::Some(*mint_authority),
+                decimals,
+                is_initialized: true,
+                ..spl_token::state::Mint::default()
+            },
+            &mut data,
+        ).unwrap();
+        TestAccount::new(key, 0, data, spl_token::id())
+    }
+
+    fn token_account(mint: &Pubkey, owner: &Pubkey, amount: u64) -> TestAccount {
+        let mut data = vec![0u8; spl_token::state::Account::LEN];
+        spl_token::state::Account::pack(
+            spl_token::state::Account {
+                mint: *mint,
+                owner: *owner,
+                amount,
+                state: spl_token::state::AccountState::Initialized,
+                ..spl_token::state::Account::default()
+            },
+            &mut data,
+        ).unwrap();
+        TestAccount::new(Pubkey::new_unique(), 0, data, spl_token::id())
+    }
+
+    fn lock_and_mint_data(amount: u64) -> Vec<u8> {
+        let mut data = vec![1u8];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.extend_from_slice(&[7u8; DESTINATION_CHAIN_ADDRESS_LEN]);
+        data
+    }
+
+    fn processed_account(program_id: &Pubkey) -> TestAccount {
+        processed_window_account(program_id, 0)
+    }
+
+    fn processed_window_account(program_id: &Pubkey, window: u64) -> TestAccount {
+        let (key, _) = Pubkey::find_program_address(&[b"Locker", b"Processed", &window.to_le_bytes()], program_id);
+        TestAccount::new(key, 0, vec![0u8; state::BITMAP_SIZE], *program_id)
+    }
+
+    fn event_marker_account(program_id: &Pubkey, eth_tx_hash: &[u8; 32], eth_log_index: u64) -> TestAccount {
+        let (key, _) = Pubkey::find_program_address(
+            &[b"Locker", b"Event", eth_tx_hash, &eth_log_index.to_le_bytes()],
+            program_id
+        );
+        TestAccount::new(key, 0, vec![], system_program::id())
+    }
+
+    fn pending_release_account(program_id: &Pubkey, sequence: u64) -> TestAccount {
+        let (key, _) = Pubkey::find_program_address(&[b"Locker", b"Release", &sequence.to_le_bytes()], program_id);
+        // allocated up front, the create_account CPI is not emulated
+        TestAccount::new(key, 0, vec![0u8; state::PENDING_RELEASE_SIZE], *program_id)
+    }
+
+    fn start_release_data(amount: u64, sequence: u64, destination: &Pubkey) -> Vec<u8> {
+        let mut data = vec![25u8];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.extend_from_slice(&sequence.to_le_bytes());
+        data.extend_from_slice(destination.as_ref());
+        data
+    }
+
+    fn fill_release_data(sequence: u64, amount: u64) -> Vec<u8> {
+        let mut data = vec![26u8];
+        data.extend_from_slice(&sequence.to_le_bytes());
+        data.extend_from_slice(&amount.to_le_bytes());
+        data
+    }
+
+    fn mint_data(amount: u64, sequence: u64) -> Vec<u8> {
+        let mut data = vec![3u8];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.extend_from_slice(&sequence.to_le_bytes());
+        data.extend_from_slice(&[0u8; 48]);
+        data
+    }
+
+    /// Waits out the timelock on the queued config change, then applies it.
+    fn apply_queued_change(program_id: &Pubkey, accounts: &mut [TestAccount]) -> ProgramResult {
+        CLOCK.with(|clock| clock.borrow_mut().slot += state::CONFIG_TIMELOCK_SLOTS);
+        process(program_id, accounts, &[21])
+    }
+
+    fn release_data(amount: u64, sequence: u64) -> Vec<u8> {
+        let mut data = vec![2u8];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.extend_from_slice(&sequence.to_le_bytes());
+        data
+    }
+
+    fn burn_and_close_data(amount: u64) -> Vec<u8> {
+        let mut data = burn_and_release_data(amount);
+        data[0] = 24;
+        data
+    }
+
+    fn burn_and_release_data(amount: u64) -> Vec<u8> {
+        let mut data = vec![4u8];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.extend_from_slice(&[7u8; DESTINATION_CHAIN_ADDRESS_LEN]);
+        data
+    }
+
+    #[test]
+    fn test_burn_more_than_balance() {
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(owner),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority: Pubkey::new_unique(),
+                total_locked: 1_000,
+                total_minted: 1_000,
+                ..Locker::default()
+            }),
+            burnlog_account(&program_id),
+            token_account(&mint, &owner, 10),
+            TestAccount::new(mint, 0, vec![], spl_token::id()),
+            TestAccount::program(spl_token::id()),
+        ];
+
+        let result = process(&program_id, &mut accounts, &burn_and_release_data(11));
+        assert_eq!(result, Err(LockerError::InsufficientTokenBalance.into()));
+    }
+
+    #[test]
+    fn test_totals_cannot_underflow() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                total_locked: 100,
+                total_minted: 5,
+                ..Locker::default()
+            }).with_lamports(vault_reserve() + 1_000),
+            burnlog_account(&program_id),
+            token_account(&mint, &authority, 10),
+            TestAccount::new(mint, 0, vec![], spl_token::id()),
+            TestAccount::program(spl_token::id()),
+        ];
+        assert_eq!(
+            process(&program_id, &mut accounts, &burn_and_release_data(6)),
+            Err(LockerError::InsufficientMinted.into())
+        );
+
+        accounts.truncate(2);
+        accounts.extend(vec![
+            TestAccount::new(destination, 0, vec![], system_program::id()),
+            TestAccount::program(system_program::id()),
+            release_record_account(&program_id, &destination),
+            processed_account(&program_id),
+        ]);
+        assert_eq!(
+            process(&program_id, &mut accounts, &release_data(101, 0)),
+            Err(LockerError::InsufficientLocked.into())
+        );
+        assert!(process(&program_id, &mut accounts, &release_data(95, 0)).is_ok());
+        let state_info = Locker::unpack(&accounts[1].data).unwrap();
+        assert_eq!((state_info.total_locked, state_info.total_minted), (5, 5));
+    }
+
+    #[test]
+    fn test_require_signers_unsigned_cosigner() {
+        let authority = Pubkey::new_unique();
+        let cosigner = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            TestAccount::new(cosigner, 0, vec![], system_program::id()),
+        ];
+        let infos: Vec<AccountInfo> = accounts.iter_mut().map(TestAccount::info).collect();
+
+        assert!(Processor::require_signers(&infos, &[&authority]).is_ok());
+        assert_eq!(
+            Processor::require_signers(&infos, &[&authority, &cosigner]),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        assert_eq!(
+            Processor::require_signers(&infos, &[&Pubkey::new_unique()]),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+
+    #[test]
+    fn test_read_log_returns_raw_bytes() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut log_info = mintlog_account(&program_id);
+        LockAndMintLog::pack(
+            LockAndMintLog {
+                amount: U256::from(1_000_000_000u64),
+                recipient: [9u8; DESTINATION_CHAIN_ADDRESS_LEN],
+                nonce: 3,
+            },
+            &mut log_info.data,
+        ).unwrap();
+        let mut accounts = vec![log_info];
+
+        assert!(process(&program_id, &mut accounts, &[6, 0]).is_ok());
+        assert_eq!(take_return_data(), Some(accounts[0].data.clone()));
+
+        let mut accounts = vec![burnlog_account(&program_id)];
+        assert_eq!(
+            process(&program_id, &mut accounts, &[6, 0]),
+            Err(LockerError::InvalidPda.into())
+        );
+    }
+
+    #[test]
+    fn test_lock_deposit_cap() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(1_000),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                total_locked: 400,
+                total_locked_cap: 1_000,
+                ..Locker::default()
+            }),
+            mintlog_account(&program_id),
+            TestAccount::program(system_program::id()),
+        ];
+
+        assert!(process(&program_id, &mut accounts, &lock_and_mint_data(600)).is_ok());
+        let state_info = Locker::unpack(&accounts[1].data).unwrap();
+        assert_eq!(state_info.total_locked, 1_000);
+
+        assert_eq!(
+            process(&program_id, &mut accounts, &lock_and_mint_data(1)),
+            Err(LockerError::DepositCapExceeded.into())
+        );
+    }
+
+    #[test]
+    fn test_zeroed_authority_rejected() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::default()),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority: Pubkey::default(),
+                total_locked: 1_000,
+                ..Locker::default()
+            }),
+            TestAccount::new(Pubkey::new_unique(), 0, vec![], system_program::id()),
+            TestAccount::program(system_program::id()),
+        ];
+
+        assert_eq!(
+            process(&program_id, &mut accounts, &release_data(100, 0)),
+            Err(LockerError::InvalidAuthority.into())
+        );
+    }
+
+    #[test]
+    fn test_reentrant_lock_rejected() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(1_000),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                in_progress: true,
+                ..Locker::default()
+            }),
+            mintlog_account(&program_id),
+            TestAccount::program(system_program::id()),
+        ];
+
+        assert_eq!(
+            process(&program_id, &mut accounts, &lock_and_mint_data(100)),
+            Err(LockerError::Reentrancy.into())
+        );
+
+        accounts[1] = state_account(&program_id, Locker {
+            is_initialized: true,
+            ..Locker::default()
+        });
+        assert!(process(&program_id, &mut accounts, &lock_and_mint_data(100)).is_ok());
+        let state_info = Locker::unpack(&accounts[1].data).unwrap();
+        assert!(!state_info.in_progress);
+    }
+
+    #[test]
+    fn test_wrongly_sized_accounts_rejected() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut short_state = state_account(&program_id, Locker {
+            is_initialized: true,
+            ..Locker::default()
+        });
+        short_state.data.truncate(state::STATESIZE - 1);
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()),
+            short_state,
+            mintlog_account(&program_id),
+            TestAccount::program(system_program::id()),
+        ];
+        assert_eq!(
+            process(&program_id, &mut accounts, &lock_and_mint_data(100)),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        accounts[1] = state_account(&program_id, Locker {
+            is_initialized: true,
+            ..Locker::default()
+        });
+        accounts[2].data.push(0);
+        assert_eq!(
+            process(&program_id, &mut accounts, &lock_and_mint_data(100)),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_release_accumulates_per_destination() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                total_locked: 1_000,
+                ..Locker::default()
+            }),
+            TestAccount::new(destination, 0, vec![], system_program::id()),
+            TestAccount::program(system_program::id()),
+            release_record_account(&program_id, &destination),
+            processed_account(&program_id),
+        ];
+        accounts[1].lamports = vault_reserve() + 1_000;
+
+        assert!(process(&program_id, &mut accounts, &release_data(100, 0)).is_ok());
+        assert!(process(&program_id, &mut accounts, &release_data(250, 1)).is_ok());
+        assert_eq!(
+            process(&program_id, &mut accounts, &release_data(250, 1)),
+            Err(LockerError::AlreadyProcessed.into())
+        );
+
+        let release_record = ReleaseRecord::unpack(&accounts[4].data).unwrap();
+        assert_eq!(release_record.destination, destination);
+        assert_eq!(release_record.total_released, 350);
+        let state_info = Locker::unpack(&accounts[1].data).unwrap();
+        assert_eq!(state_info.total_locked, 650);
+        assert_eq!(accounts[1].lamports, vault_reserve() + 650);
+        assert_eq!(accounts[2].lamports, 350);
+    }
+
+    #[test]
+    fn test_transfer_from_vault_by_owner() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let reserve = Rent::default().minimum_balance(0);
+        let mut accounts = vec![
+            TestAccount::new(Pubkey::new_unique(), reserve + 1_000, vec![], program_id),
+            TestAccount::new(Pubkey::new_unique(), 0, vec![], system_program::id()),
+            TestAccount::new(Pubkey::new_unique(), 1_000, vec![], system_program::id()),
+        ];
+        let infos: Vec<AccountInfo> = accounts.iter_mut().map(TestAccount::info).collect();
+        assert!(Processor::transfer_from_vault(&infos[0], &infos[1], 300, &program_id).is_ok());
+        // the rent reserve is never released
+        assert_eq!(
+            Processor::transfer_from_vault(&infos[0], &infos[1], 701, &program_id),
+            Err(LockerError::InsufficientLiquidity.into())
+        );
+        // a vault the program does not own cannot be debited
+        assert_eq!(
+            Processor::transfer_from_vault(&infos[2], &infos[1], 300, &program_id),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        drop(infos);
+        assert_eq!(accounts[0].lamports, reserve + 700);
+        assert_eq!(accounts[1].lamports, 300);
+        assert_eq!(accounts[2].lamports, 1_000);
+    }
+
+    #[test]
+    fn test_decimals_boundary() {
+        assert_eq!(
+            Processor::underlying_amount_from_spl_amount(38, 9, 1),
+            Ok(U256::exp10(29))
+        );
+        assert_eq!(
+            Processor::underlying_amount_from_spl_amount(39, 9, 1),
+            Err(LockerError::InvalidDecimals.into())
+        );
+        assert_eq!(
+            Processor::spl_amount_from_underlying_amount(38, 38, U256::from(5u64)),
+            Ok(5)
+        );
+        assert_eq!(
+            Processor::spl_amount_from_underlying_amount(18, 39, U256::from(5u64)),
+            Err(LockerError::InvalidDecimals.into())
+        );
+    }
+
+    #[test]
+    fn test_collateralization_ratio_after_lock_and_mint() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut state_info = state_account(&program_id, Locker {
+            is_initialized: true,
+            authority,
+            collateralization_ratio: u64::MAX,
+            ..Locker::default()
+        });
+
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(1_000),
+            state_info,
+            mintlog_account(&program_id),
+            TestAccount::program(system_program::id()),
+        ];
+        assert!(process(&program_id, &mut accounts, &lock_and_mint_data(1_000)).is_ok());
+        assert_eq!(Locker::unpack(&accounts[1].data).unwrap().collateralization_ratio, u64::MAX);
+        state_info = accounts.swap_remove(1);
+
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_info,
+            token_account(&mint, &Pubkey::new_unique(), 0),
+            mint_account(mint, &authority),
+            TestAccount::program(spl_token::id()),
+            processed_account(&program_id),
+            TestAccount::program(system_program::id()),
+            event_marker_account(&program_id, &[0u8; 32], 0),
+        ];
+        assert!(process(&program_id, &mut accounts, &mint_data(500, 0)).is_ok());
+        let state_info = Locker::unpack(&accounts[1].data).unwrap();
+        assert_eq!(state_info.total_minted, 500);
+        assert_eq!(state_info.collateralization_ratio, 20_000);
+    }
+
+    #[test]
+    fn test_backup_authority_permissions() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let backup_authority = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(backup_authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                backup_authority,
+                total_locked: 1_000,
+                ..Locker::default()
+            }).with_lamports(vault_reserve() + 1_000),
+            TestAccount::new(destination, 0, vec![], system_program::id()),
+            TestAccount::program(system_program::id()),
+            release_record_account(&program_id, &destination),
+            processed_account(&program_id),
+        ];
+        assert!(process(&program_id, &mut accounts, &release_data(100, 0)).is_ok());
+
+        let mut set_backup_data = vec![8u8];
+        set_backup_data.extend_from_slice(Pubkey::new_unique().as_ref());
+        accounts.truncate(2);
+        assert_eq!(
+            process(&program_id, &mut accounts, &set_backup_data),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        accounts[0] = TestAccount::signer(authority);
+        assert!(process(&program_id, &mut accounts, &set_backup_data).is_ok());
+        assert!(apply_queued_change(&program_id, &mut accounts).is_ok());
+        let state_info = Locker::unpack(&accounts[1].data).unwrap();
+        assert_eq!(state_info.backup_authority.as_ref(), &set_backup_data[1..]);
+    }
+
+    #[test]
+    fn test_mint_replay_rejected() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                total_locked: 1_000,
+                ..Locker::default()
+            }),
+            token_account(&mint, &Pubkey::new_unique(), 0),
+            mint_account(mint, &authority),
+            TestAccount::program(spl_token::id()),
+            processed_account(&program_id),
+            TestAccount::program(system_program::id()),
+            event_marker_account(&program_id, &[0u8; 32], 0),
+        ];
+
+        assert!(process(&program_id, &mut accounts, &mint_data(100, 9)).is_ok());
+        assert_eq!(
+            process(&program_id, &mut accounts, &mint_data(100, 9)),
+            Err(LockerError::AlreadyProcessed.into())
+        );
+        assert!(process(&program_id, &mut accounts, &mint_data(100, 10)).is_ok());
+        assert_eq!(accounts[5].data[1], 0b0000_0110);
+    }
+
+    #[test]
+    fn test_processed_bitmap_windows() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                total_locked: 1_000,
+                ..Locker::default()
+            }),
+            token_account(&mint, &Pubkey::new_unique(), 0),
+            mint_account(mint, &authority),
+            TestAccount::program(spl_token::id()),
+            processed_account(&program_id),
+            TestAccount::program(system_program::id()),
+            event_marker_account(&program_id, &[0u8; 32], 0),
+        ];
+        let last_in_window = state::BITS_PER_ACCOUNT - 1;
+        assert!(process(&program_id, &mut accounts, &mint_data(100, last_in_window)).is_ok());
+        assert!(state::is_set(&accounts[5].data, last_in_window));
+
+        // the next sequence lives in the second window's account
+        let mut data = mint_data(100, state::BITS_PER_ACCOUNT);
+        data[49..57].copy_from_slice(&1u64.to_le_bytes());
+        accounts[7] = event_marker_account(&program_id, &[0u8; 32], 1);
+        assert_eq!(
+            process(&program_id, &mut accounts, &data),
+            Err(LockerError::InvalidPda.into())
+        );
+        accounts[5] = processed_window_account(&program_id, 1);
+        assert!(process(&program_id, &mut accounts, &data).is_ok());
+        assert!(state::is_set(&accounts[5].data, 0));
+        assert_eq!(accounts[5].data.len(), state::BITMAP_SIZE);
+    }
+
+    #[test]
+    fn test_mint_event_marker_rejects_second_relayer() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let eth_tx_hash = [0x11u8; 32];
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                total_locked: 1_000,
+                ..Locker::default()
+            }),
+            token_account(&mint, &Pubkey::new_unique(), 0),
+            mint_account(mint, &authority),
+            TestAccount::program(spl_token::id()),
+            processed_account(&program_id),
+            TestAccount::program(system_program::id()),
+            event_marker_account(&program_id, &eth_tx_hash, 4),
+        ];
+        let data = |sequence: u64, eth_log_index: u64| {
+            let mut data = mint_data(100, sequence);
+            data[17..49].copy_from_slice(&eth_tx_hash);
+            data[49..57].copy_from_slice(&eth_log_index.to_le_bytes());
+            data
+        };
+
+        take_invoked();
+        assert!(process(&program_id, &mut accounts, &data(0, 4)).is_ok());
+        let create_marker = take_invoked()
+            .into_iter()
+            .find(|ix| ix.program_id == system_program::id())
+            .unwrap();
+        assert_eq!(create_marker.accounts[1].pubkey, accounts[7].key);
+
+        // the create_account CPI is not emulated, hand the marker over by hand
+        accounts[7].owner = program_id;
+        assert_eq!(
+            process(&program_id, &mut accounts, &data(1, 4)),
+            Err(LockerError::AlreadyProcessed.into())
+        );
+        assert_eq!(
+            process(&program_id, &mut accounts, &data(1, 5)),
+            Err(LockerError::InvalidPda.into())
+        );
+        accounts[7] = event_marker_account(&program_id, &eth_tx_hash, 5);
+        assert!(process(&program_id, &mut accounts, &data(1, 5)).is_ok());
+        assert_eq!(Locker::unpack(&accounts[1].data).unwrap().total_minted, 200);
+    }
+
+    #[test]
+    fn test_prefunded_event_marker_is_still_created() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let eth_tx_hash = [0x22u8; 32];
+        let marker_rent = Rent::default().minimum_balance(0);
+        let mut accounts = vec![
+            TestAccount::signer(authority).with_lamports(marker_rent),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                total_locked: 1_000,
+                ..Locker::default()
+            }),
+            token_account(&mint, &Pubkey::new_unique(), 0),
+            mint_account(mint, &authority),
+            TestAccount::program(spl_token::id()),
+            processed_account(&program_id),
+            TestAccount::program(system_program::id()),
+            // dust sent ahead of the mint must not block the marker
+            event_marker_account(&program_id, &eth_tx_hash, 4).with_lamports(1),
+        ];
+        let mut data = mint_data(100, 0);
+        data[17..49].copy_from_slice(&eth_tx_hash);
+        data[49..57].copy_from_slice(&4u64.to_le_bytes());
+
+        take_invoked();
+        assert!(process(&program_id, &mut accounts, &data).is_ok());
+        let marker = accounts[7].key;
+        let system_ixs: Vec<_> = take_invoked()
+            .into_iter()
+            .filter(|ix| ix.program_id == system_program::id())
+            .collect();
+        assert_eq!(
+            system_ixs,
+            vec![
+                system_instruction::transfer(&authority, &marker, marker_rent - 1),
+                system_instruction::allocate(&marker, 0),
+                system_instruction::assign(&marker, &program_id),
+            ]
+        );
+        assert_eq!(accounts[7].lamports, marker_rent);
+    }
+
+    #[test]
+    fn test_equal_decimals_conversion_is_exact() {
+        let underlying_amount = Processor::underlying_amount_from_spl_amount(9, 9, u64::MAX).unwrap();
+        assert_eq!(underlying_amount, U256::from(u64::MAX));
+        assert_eq!(
+            Processor::spl_amount_from_underlying_amount(9, 9, underlying_amount),
+            Ok(u64::MAX)
+        );
+        assert_eq!(
+            Processor::spl_amount_from_underlying_amount(9, 9, underlying_amount + 1),
+            Err(LockerError::UnexpectedDecimalConversion.into())
+        );
+    }
+
+    #[test]
+    fn test_mint_signs_with_pda_after_claim() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let (state_pubkey, _) = Pubkey::find_program_address(&[b"Locker", b"Init"], &program_id);
+        let locker = Locker {
+            is_initialized: true,
+            authority,
+            total_locked: 1_000,
+            ..Locker::default()
+        };
+
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, locker),
+            mint_account(mint, &authority),
+            TestAccount::program(spl_token::id()),
+        ];
+        take_invoked();
+        assert!(process(&program_id, &mut accounts, &[9]).is_ok());
+        let invoked = take_invoked();
+        assert_eq!(invoked.len(), 1);
+        assert_eq!(invoked[0].program_id, spl_token::id());
+        assert_eq!(invoked[0].accounts[0].pubkey, mint);
+        let locker = Locker::unpack(&accounts[1].data).unwrap();
+        assert_eq!(locker.mint, mint);
+
+        // once pinned, no other mint can be handed to the PDA
+        let other_mint = Pubkey::new_unique();
+        accounts[2] = mint_account(other_mint, &authority);
+        assert_eq!(
+            process(&program_id, &mut accounts, &[9]),
+            Err(LockerError::InvalidMint.into())
+        );
+
+        // the token program is stubbed, so hand the authority over by hand
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, locker),
+            token_account(&mint, &Pubkey::new_unique(), 0),
+            mint_account(mint, &state_pubkey),
+            TestAccount::program(spl_token::id()),
+            processed_account(&program_id),
+            TestAccount::program(system_program::id()),
+            event_marker_account(&program_id, &[0u8; 32], 0),
+        ];
+        assert!(process(&program_id, &mut accounts, &mint_data(100, 0)).is_ok());
+        let invoked = take_invoked();
+        let mint_to = invoked.last().unwrap();
+        assert_eq!(mint_to.accounts[2].pubkey, state_pubkey);
+        assert!(mint_to.accounts[2].is_signer);
+
+        // nor can a mint the PDA was made authority of behind its back
+        accounts[3] = mint_account(other_mint, &state_pubkey);
+        assert_eq!(
+            process(&program_id, &mut accounts, &mint_data(100, 1)),
+            Err(LockerError::InvalidMint.into())
+        );
+
+        // the locker authority cannot claim a mint it does not control
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, locker),
+            mint_account(mint, &Pubkey::new_unique()),
+            TestAccount::program(spl_token::id()),
+        ];
+        assert_eq!(
+            process(&program_id, &mut accounts, &[9]),
+            Err(LockerError::InvalidAuthority.into())
+        );
+    }
+
+    #[test]
+    fn test_admin_burn_from_pda_account() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let (state_pubkey, _) = Pubkey::find_program_address(&[b"Locker", b"Init"], &program_id);
+        let locker = Locker {
+            is_initialized: true,
+            authority,
+            total_locked: 1_000,
+            total_minted: 1_000,
+            ..Locker::default()
+        };
+        let mut data = vec![10u8];
+        data.extend_from_slice(&400u64.to_le_bytes());
+
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, locker),
+            token_account(&mint, &Pubkey::new_unique(), 400),
+            mint_account(mint, &state_pubkey),
+            TestAccount::program(spl_token::id()),
+        ];
+        assert_eq!(
+            process(&program_id, &mut accounts, &data),
+            Err(ProgramError::IllegalOwner)
+        );
+
+        accounts[2] = token_account(&mint, &state_pubkey, 400);
+        take_invoked();
+        assert!(process(&program_id, &mut accounts, &data).is_ok());
+        let burn = take_invoked().pop().unwrap();
+        assert_eq!(burn.accounts[2].pubkey, state_pubkey);
+        assert!(burn.accounts[2].is_signer);
+
+        let locker = Locker::unpack(&accounts[1].data).unwrap();
+        assert_eq!(locker.total_minted, 600);
+        assert!(!locker.in_progress);
+    }
+
+    #[test]
+    fn test_lamports_conserved_across_lock_and_release() {
+        setup_syscall_stubs();
+        // the locker charges no fees, so every lamport locked must come back
+        for &amount in [1u64, 500, 1_000].iter() {
+            let program_id = Pubkey::new_unique();
+            let authority = Pubkey::new_unique();
+            let user = Pubkey::new_unique();
+            let mut accounts = vec![
+                TestAccount::signer(user).with_lamports(1_000),
+                state_account(&program_id, Locker {
+                    is_initialized: true,
+                    authority,
+                    ..Locker::default()
+                }).with_lamports(vault_reserve()),
+                mintlog_account(&program_id),
+                TestAccount::program(system_program::id()),
+                TestAccount::signer(authority),
+                release_record_account(&program_id, &user),
+                processed_account(&program_id),
+            ];
+            let total = |accounts: &[TestAccount]| accounts.iter().map(|a| a.lamports).sum::<u64>();
+            let before = total(&accounts);
+            let state_before = accounts[1].lamports;
+
+            assert!(process(&program_id, &mut accounts[..4], &lock_and_mint_data(amount)).is_ok());
+            assert_eq!(total(&accounts), before);
+            assert_eq!(accounts[1].lamports, state_before + amount);
+
+            // reorder to authority, state, user, system, release record,
+            // processed, mintlog for the release
+            accounts.swap(0, 4);
+            accounts.swap(2, 4);
+            accounts.swap(4, 5);
+            accounts.swap(5, 6);
+            assert!(process(&program_id, &mut accounts[..6], &release_data(amount, 0)).is_ok());
+            assert_eq!(total(&accounts), before);
+            assert_eq!(accounts[1].lamports, state_before);
+            assert_eq!(accounts[2].lamports, 1_000);
+            assert_eq!(Locker::unpack(&accounts[1].data).unwrap().total_locked, 0);
+        }
+    }
+
+    #[test]
+    fn test_lock_returns_receipt() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(1_000),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority: Pubkey::new_unique(),
+                total_locked: 50,
+                outbound_nonce: 4,
+                ..Locker::default()
+            }),
+            mintlog_account(&program_id),
+            TestAccount::program(system_program::id()),
+        ];
+        assert!(process(&program_id, &mut accounts, &lock_and_mint_data(300)).is_ok());
+
+        // the receipt follows the InstructionResult in the return data
+        let returned = take_return_data().unwrap();
+        assert_eq!(returned.len(), state::INSTRUCTION_RESULT_SIZE + state::RECEIPTSIZE);
+        let result = InstructionResult::unpack_from_slice(&returned).unwrap();
+        assert_eq!(result.amount, 300);
+        assert_eq!(result.total_locked, 350);
+        let receipt = LockReceipt::unpack_from_slice(&returned[state::INSTRUCTION_RESULT_SIZE..]).unwrap();
+        let log = LockAndMintLog::unpack_from_slice(&accounts[2].data).unwrap();
+        assert_eq!(receipt.nonce, 4);
+        assert_eq!(receipt.nonce, log.nonce);
+        assert_eq!(receipt.amount, 300);
+        assert_eq!(receipt.underlying_amount, log.amount);
+        assert_eq!(receipt.recipient, log.recipient);
+        assert_eq!(receipt.total_locked, 350);
+        assert_eq!(receipt.vault_balance, accounts[1].lamports);
+    }
+
+    #[test]
+    fn test_account_owner_and_writability() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(1_000),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority: Pubkey::new_unique(),
+                ..Locker::default()
+            }),
+            mintlog_account(&program_id),
+            TestAccount::program(system_program::id()),
+        ];
+
+        accounts[1].owner = Pubkey::new_unique();
+        assert_eq!(
+            process(&program_id, &mut accounts, &lock_and_mint_data(100)),
+            Err(LockerError::InvalidAccountOwner.into())
+        );
+        assert_eq!(
+            process(&program_id, &mut accounts[1..2], &[13]),
+            Err(LockerError::InvalidAccountOwner.into())
+        );
+        accounts[1].owner = program_id;
+
+        accounts[1].is_writable = false;
+        assert_eq!(
+            process(&program_id, &mut accounts, &lock_and_mint_data(100)),
+            Err(LockerError::AccountNotWritable.into())
+        );
+        // getters only read the state
+        assert!(process(&program_id, &mut accounts[1..2], &[13]).is_ok());
+        accounts[1].is_writable = true;
+
+        accounts[2].is_writable = false;
+        assert_eq!(
+            process(&program_id, &mut accounts, &lock_and_mint_data(100)),
+            Err(LockerError::AccountNotWritable.into())
+        );
+        accounts[2].is_writable = true;
+        assert!(process(&program_id, &mut accounts, &lock_and_mint_data(100)).is_ok());
+    }
+
+    #[test]
+    fn test_outbound_nonce_per_lock() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(1_000),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority: Pubkey::new_unique(),
+                outbound_nonce: 7,
+                ..Locker::default()
+            }),
+            mintlog_account(&program_id),
+            TestAccount::program(system_program::id()),
+        ];
+        take_logged();
+        for nonce in 7..9u64 {
+            assert!(process(&program_id, &mut accounts, &lock_and_mint_data(100)).is_ok());
+            assert_eq!(LockAndMintLog::unpack_from_slice(&accounts[2].data).unwrap().nonce, nonce);
+            let logged = take_logged();
+            let message = array_ref![logged[0][2], 0, wire::MESSAGE_LEN];
+            assert_eq!(wire::decode_lock(message).2, nonce);
+        }
+        assert_eq!(Locker::unpack(&accounts[1].data).unwrap().outbound_nonce, 9);
+    }
+
+    #[test]
+    fn test_large_release_needs_backup_signature() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let backup = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                backup_authority: backup,
+                total_locked: 1_000,
+                large_release_threshold: 100,
+                ..Locker::default()
+            }).with_lamports(vault_reserve() + 1_000),
+            TestAccount::new(destination, 0, vec![], system_program::id()),
+            TestAccount::program(system_program::id()),
+            release_record_account(&program_id, &destination),
+            processed_account(&program_id),
+            TestAccount::new(backup, 0, vec![], system_program::id()),
+        ];
+
+        assert!(process(&program_id, &mut accounts, &release_data(100, 0)).is_ok());
+        assert_eq!(
+            process(&program_id, &mut accounts, &release_data(101, 1)),
+            Err(LockerError::InsufficientSigners.into())
+        );
+
+        accounts[6].is_signer = true;
+        assert!(process(&program_id, &mut accounts, &release_data(101, 1)).is_ok());
+        assert_eq!(accounts[2].lamports, 201);
+    }
+
+    #[test]
+    fn test_verify_pda() {
+        let program_id = Pubkey::new_unique();
+        let (key, bump) = Pubkey::find_program_address(&[b"Locker", b"Init"], &program_id);
+        let mut account = TestAccount::new(key, 0, vec![], program_id);
+        assert_eq!(
+            Processor::verify_pda(&account.info(), &[b"Locker", b"Init"], &program_id),
+            Ok(bump)
+        );
+        assert_eq!(
+            Processor::verify_pda(&account.info(), &[b"Locker", b"Mint"], &program_id),
+            Err(LockerError::InvalidPda.into())
+        );
+        assert_eq!(
+            Processor::verify_pda(&account.info(), &[b"Locker", b"Init"], &Pubkey::new_unique()),
+            Err(LockerError::InvalidPda.into())
+        );
+    }
+
+    #[test]
+    fn test_precreated_release_record() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()),
+            TestAccount::new(destination, 0, vec![], system_program::id()),
+            release_record_account(&program_id, &destination),
+            TestAccount::program(system_program::id()),
+        ];
+        assert!(process(&program_id, &mut accounts, &[12]).is_ok());
+        let release_record = ReleaseRecord::unpack(&accounts[2].data).unwrap();
+        assert_eq!(release_record.destination, destination);
+        assert_eq!(release_record.total_released, 0);
+
+        // creating it again is a no-op
+        take_invoked();
+        assert!(process(&program_id, &mut accounts, &[12]).is_ok());
+        assert!(take_invoked().is_empty());
+        let release_record_account = accounts.swap_remove(2);
+
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                total_locked: 1_000,
+                ..Locker::default()
+            }).with_lamports(vault_reserve() + 1_000),
+            TestAccount::new(destination, 0, vec![], system_program::id()),
+            TestAccount::program(system_program::id()),
+            release_record_account,
+            processed_account(&program_id),
+        ];
+        assert!(process(&program_id, &mut accounts, &release_data(100, 0)).is_ok());
+        // the vault pays out directly, so nothing was created along the way
+        assert!(take_invoked().is_empty());
+        assert_eq!(ReleaseRecord::unpack(&accounts[4].data).unwrap().total_released, 100);
+    }
+
+    #[test]
+    fn test_in_flight_after_lock() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(1_000),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority: Pubkey::new_unique(),
+                total_locked: 500,
+                total_minted: 500,
+                ..Locker::default()
+            }),
+            mintlog_account(&program_id),
+            TestAccount::program(system_program::id()),
+        ];
+        assert!(process(&program_id, &mut accounts, &lock_and_mint_data(300)).is_ok());
+
+        let mut accounts = vec![accounts.swap_remove(1)];
+        assert!(process(&program_id, &mut accounts, &[13]).is_ok());
+        let in_flight = U256::from_big_endian(&take_return_data().unwrap());
+        assert_eq!(in_flight, U256::from(300u64) * U256::from(1_000_000_000u64));
+
+        let mut locker = Locker::unpack(&accounts[0].data).unwrap();
+        locker.total_minted = locker.total_locked + 1;
+        Locker::pack(locker, &mut accounts[0].data).unwrap();
+        assert_eq!(
+            process(&program_id, &mut accounts, &[13]),
+            Err(LockerError::ArithmeticOverflow.into())
+        );
+    }
+
+    #[test]
+    fn test_in_flight_across_decimals() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        // 1 SOL locked, half a token of a 6 decimal mint minted against it
+        let mut accounts = vec![state_account(&program_id, Locker {
+            is_initialized: true,
+            total_locked: 1_000_000_000,
+            total_minted: 500_000,
+            mint_decimals: 6,
+            ..Locker::default()
+        })];
+        assert!(process(&program_id, &mut accounts, &[13]).is_ok());
+        let in_flight = U256::from_big_endian(&take_return_data().unwrap());
+        assert_eq!(in_flight, U256::exp10(17) * U256::from(5u64));
+
+        // and against an underlying token with fewer decimals than lamports
+        let mut locker = Locker::unpack(&accounts[0].data).unwrap();
+        locker.underlying_decimals = 6;
+        Locker::pack(locker, &mut accounts[0].data).unwrap();
+        assert!(process(&program_id, &mut accounts, &[13]).is_ok());
+        assert_eq!(U256::from_big_endian(&take_return_data().unwrap()), U256::from(500_000u64));
+    }
+
+    #[test]
+    fn test_preview_release_reports_liquidity() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let mut accounts = vec![
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority: Pubkey::new_unique(),
+                total_locked: 1_000,
+                ..Locker::default()
+            }).with_lamports(vault_reserve() + 400),
+            release_record_account(&program_id, &destination),
+        ];
+        let preview = |amount: u64, accounts: &mut [TestAccount]| {
+            let mut data = vec![14u8];
+            data.extend_from_slice(&amount.to_le_bytes());
+            data.extend_from_slice(destination.as_ref());
+            assert!(process(&program_id, accounts, &data).is_ok());
+            let status = take_return_data().unwrap();
+            u64::from_le_bytes(*array_ref![status, 0, 8])
+        };
+
+        assert_eq!(preview(400, &mut accounts), 0);
+        assert_eq!(
+            preview(401, &mut accounts),
+            u64::from(ProgramError::from(LockerError::InsufficientLiquidity))
+        );
+        assert_eq!(
+            preview(1_001, &mut accounts),
+            u64::from(ProgramError::from(LockerError::InsufficientLocked))
+        );
+        // nothing moved
+        assert_eq!(accounts[0].lamports, vault_reserve() + 400);
+    }
+
+    #[test]
+    fn test_undersized_log_account() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(1_000),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority: Pubkey::new_unique(),
+                ..Locker::default()
+            }),
+            mintlog_account(&program_id),
+            TestAccount::program(system_program::id()),
+        ];
+        accounts[2].data.truncate(state::LOGSIZE - 4);
+        assert_eq!(
+            process(&program_id, &mut accounts, &lock_and_mint_data(100)),
+            Err(LockerError::LogAccountTooSmall.into())
+        );
+
+        let mut accounts = vec![accounts.swap_remove(2)];
+        assert_eq!(
+            process(&program_id, &mut accounts, &[6, 0]),
+            Err(LockerError::LogAccountTooSmall.into())
+        );
+    }
+
+    #[test]
+    fn test_resize_log_account() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let locker = Locker {
+            is_initialized: true,
+            authority,
+            ..Locker::default()
+        };
+        let mut old_log = mintlog_account(&program_id);
+        old_log.data = vec![0xffu8; state::LOGSIZE - 4];
+        let mut accounts = vec![
+            TestAccount::signer(authority).with_lamports(10_000_000),
+            state_account(&program_id, locker),
+            old_log,
+            TestAccount::program(system_program::id()),
+        ];
+        assert!(process(&program_id, &mut accounts, &[15, 0]).is_ok());
+        assert_eq!(accounts[2].data.len(), state::LOGSIZE);
+        assert_eq!(accounts[2].lamports, Rent::default().minimum_balance(state::LOGSIZE));
+        assert_eq!(LockAndMintLog::unpack_from_slice(&accounts[2].data).unwrap(), LockAndMintLog::default());
+
+        let mintlog = accounts.swap_remove(2);
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(1_000),
+            state_account(&program_id, locker),
+            mintlog,
+            TestAccount::program(system_program::id()),
+        ];
+        assert!(process(&program_id, &mut accounts, &lock_and_mint_data(100)).is_ok());
+        let log = LockAndMintLog::unpack_from_slice(&accounts[2].data).unwrap();
+        assert_eq!(log.amount, U256::from(100u64) * U256::from(1_000_000_000u64));
+    }
+
+    #[test]
+    fn test_migrate_legacy_state() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mut legacy_state = state_account(&program_id, Locker::default());
+        legacy_state.data = vec![1u8];
+        legacy_state.data.extend_from_slice(authority.as_ref());
+        legacy_state.data.extend_from_slice(&1_000u64.to_le_bytes());
+        legacy_state.data.extend_from_slice(&400u64.to_le_bytes());
+        legacy_state.lamports = Rent::default().minimum_balance(state::LEGACY_STATESIZE) + 1_000;
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(10_000_000),
+            legacy_state,
+            TestAccount::program(system_program::id()),
+        ];
+        assert_eq!(
+            process(&program_id, &mut accounts, &[35]),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        accounts[0] = TestAccount::signer(authority).with_lamports(10_000_000);
+        assert!(process(&program_id, &mut accounts, &[35]).is_ok());
+        assert_eq!(accounts[1].data.len(), state::STATESIZE);
+        assert_eq!(accounts[1].lamports, vault_reserve() + 1_000);
+        let state_info = Locker::unpack(&accounts[1].data).unwrap();
+        assert_eq!(state_info, Locker {
+            is_initialized: true,
+            authority,
+            total_locked: 1_000,
+            total_minted: 400,
+            collateralization_ratio: state_info.compute_collateralization_ratio().unwrap(),
+            ..Locker::default()
+        });
+
+        // a migrated state is left alone
+        let migrated = accounts[1].data.clone();
+        assert!(process(&program_id, &mut accounts, &[35]).is_ok());
+        assert_eq!(accounts[1].data, migrated);
+    }
+
+    #[test]
+    fn test_health_summary_after_lock_and_mint() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(1_000),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                collateralization_ratio: u64::MAX,
+                ..Locker::default()
+            }),
+            mintlog_account(&program_id),
+            TestAccount::program(system_program::id()),
+        ];
+        assert!(process(&program_id, &mut accounts, &lock_and_mint_data(1_000)).is_ok());
+        let state_info = accounts.swap_remove(1);
+
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_info,
+            token_account(&mint, &Pubkey::new_unique(), 0),
+            mint_account(mint, &authority),
+            TestAccount::program(spl_token::id()),
+            processed_account(&program_id),
+            TestAccount::program(system_program::id()),
+            event_marker_account(&program_id, &[0u8; 32], 0),
+        ];
+        assert!(process(&program_id, &mut accounts, &mint_data(400, 0)).is_ok());
+
+        let mut accounts = vec![accounts.swap_remove(1)];
+        assert!(process(&program_id, &mut accounts, &[16]).is_ok());
+        let summary = HealthSummary::unpack_from_slice(&take_return_data().unwrap()).unwrap();
+        let state_info = Locker::unpack(&accounts[0].data).unwrap();
+        assert_eq!(summary, HealthSummary {
+            total_locked: 1_000,
+            total_minted: 400,
+            collateralization_ratio: state_info.collateralization_ratio,
+            in_flight: U256::from(600u64) * U256::from(1_000_000_000u64),
+            vault_balance: accounts[0].lamports,
+            in_progress: false,
+        });
+        assert_eq!(summary.collateralization_ratio, 25_000);
+    }
+
+    #[test]
+    fn test_lock_amount_endianness() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let amount = 0x0102030405060708u64;
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(amount),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority: Pubkey::new_unique(),
+                ..Locker::default()
+            }),
+            mintlog_account(&program_id),
+            TestAccount::program(system_program::id()),
+        ];
+        // instruction amounts are little-endian on the wire
+        let data = lock_and_mint_data(amount);
+        assert_eq!(&data[1..9], &[8, 7, 6, 5, 4, 3, 2, 1]);
+        assert!(process(&program_id, &mut accounts, &data).is_ok());
+
+        // while the logged U256 is big-endian, as the Ethereum side reads it
+        let expected = U256::from(amount) * U256::from(1_000_000_000u64);
+        let mut expected_bytes = [0u8; 32];
+        expected.to_big_endian(&mut expected_bytes);
+        let amount_slot = &accounts[2].data[state::MAGIC_LEN..state::MAGIC_LEN + state::LOG_AMOUNT_LEN];
+        assert_eq!(amount_slot, &expected_bytes[..]);
+        assert_eq!(LockAndMintLog::unpack_from_slice(&accounts[2].data).unwrap().amount, expected);
+        assert_eq!(Locker::unpack(&accounts[1].data).unwrap().total_locked, amount);
+    }
+
+    #[test]
+    fn test_lock_of_zero_underlying_rejected() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(1_000),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority: Pubkey::new_unique(),
+                ..Locker::default()
+            }),
+            mintlog_account(&program_id),
+            TestAccount::program(system_program::id()),
+        ];
+        assert_eq!(
+            process(&program_id, &mut accounts, &lock_and_mint_data(0)),
+            Err(LockerError::AmountTooSmall.into())
+        );
+        assert_eq!(accounts[0].lamports, 1_000);
+        assert!(process(&program_id, &mut accounts, &lock_and_mint_data(1)).is_ok());
+
+        // against a 6 decimal underlying token anything under 1_000 lamports
+        // truncates to nothing
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(2_000),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority: Pubkey::new_unique(),
+                underlying_decimals: 6,
+                ..Locker::default()
+            }),
+            mintlog_account(&program_id),
+            TestAccount::program(system_program::id()),
+        ];
+        assert_eq!(
+            process(&program_id, &mut accounts, &lock_and_mint_data(999)),
+            Err(LockerError::AmountTooSmall.into())
+        );
+        assert_eq!(accounts[0].lamports, 2_000);
+        assert!(process(&program_id, &mut accounts, &lock_and_mint_data(1_999)).is_ok());
+        let log_info = LockAndMintLog::unpack_from_slice(&accounts[2].data).unwrap();
+        assert_eq!(log_info.amount, U256::from(1u64));
+    }
+
+    #[test]
+    fn test_record_and_claim_mint() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let sequence = 5u64;
+        let eth_tx_hash = [0x11u8; 32];
+        let (state_pubkey, _) = Pubkey::find_program_address(&[b"Locker", b"Init"], &program_id);
+        let (claim_pubkey, _) = Pubkey::find_program_address(
+            &[b"Locker", b"Claim", &eth_tx_hash, &3u64.to_le_bytes()],
+            &program_id
+        );
+
+        let mut record_data = vec![17u8];
+        record_data.extend_from_slice(&100u64.to_le_bytes());
+        record_data.extend_from_slice(&sequence.to_le_bytes());
+        record_data.extend_from_slice(recipient.as_ref());
+        record_data.extend_from_slice(&eth_tx_hash);
+        record_data.extend_from_slice(&3u64.to_le_bytes());
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                total_locked: 1_000,
+                ..Locker::default()
+            }),
+            // allocated up front, the create_account CPI is not emulated
+            TestAccount::new(claim_pubkey, 0, vec![0u8; state::CLAIM_SIZE], program_id),
+            processed_account(&program_id),
+            TestAccount::program(system_program::id()),
+            event_marker_account(&program_id, &eth_tx_hash, 3),
+        ];
+        assert!(process(&program_id, &mut accounts, &record_data).is_ok());
+        assert_eq!(
+            process(&program_id, &mut accounts, &record_data),
+            Err(LockerError::AlreadyProcessed.into())
+        );
+
+        // a second relayer recording the same event under a fresh sequence
+        // still lands on the event marker
+        accounts[3] = processed_account(&program_id);
+        accounts[5].owner = program_id;
+        assert_eq!(
+            process(&program_id, &mut accounts, &record_data),
+            Err(LockerError::AlreadyProcessed.into())
+        );
+        let claim_account = accounts.swap_remove(2);
+        let state_info = accounts.swap_remove(1);
+
+        let mut claim_data = vec![18u8];
+        claim_data.extend_from_slice(&eth_tx_hash);
+        claim_data.extend_from_slice(&3u64.to_le_bytes());
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()),
+            state_info,
+            claim_account,
+            token_account(&mint, &recipient, 0),
+            mint_account(mint, &state_pubkey),
+            TestAccount::program(spl_token::id()),
+        ];
+        assert_eq!(
+            process(&program_id, &mut accounts, &claim_data),
+            Err(LockerError::InvalidAuthority.into())
+        );
+
+        accounts[0] = TestAccount::signer(recipient);
+        take_invoked();
+        assert!(process(&program_id, &mut accounts, &claim_data).is_ok());
+        let mint_to = take_invoked().pop().unwrap();
+        assert_eq!(mint_to.accounts[2].pubkey, state_pubkey);
+        assert!(Claim::unpack(&accounts[2].data).unwrap().claimed);
+        assert_eq!(Locker::unpack(&accounts[1].data).unwrap().total_minted, 100);
+
+        assert_eq!(
+            process(&program_id, &mut accounts, &claim_data),
+            Err(LockerError::AlreadyProcessed.into())
+        );
+    }
+
+    #[test]
+    fn test_claim_mint_requires_solvency() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let eth_tx_hash = [0x33u8; 32];
+        let (state_pubkey, _) = Pubkey::find_program_address(&[b"Locker", b"Init"], &program_id);
+        let (claim_pubkey, _) = Pubkey::find_program_address(
+            &[b"Locker", b"Claim", &eth_tx_hash, &0u64.to_le_bytes()],
+            &program_id
+        );
+        let mut claim = vec![0u8; state::CLAIM_SIZE];
+        Claim::pack(Claim {
+            is_initialized: true,
+            claimed: false,
+            recipient,
+            amount: 100,
+            sequence: 0,
+            eth_tx_hash,
+            eth_log_index: 0,
+        }, &mut claim).unwrap();
+        let mut accounts = vec![
+            TestAccount::signer(recipient),
+            // the claim was recorded, but the lock behind it has since been
+            // released again
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                total_locked: 99,
+                ..Locker::default()
+            }),
+            TestAccount::new(claim_pubkey, 0, claim, program_id),
+            token_account(&mint, &recipient, 0),
+            mint_account(mint, &state_pubkey),
+            TestAccount::program(spl_token::id()),
+        ];
+        let mut claim_data = vec![18u8];
+        claim_data.extend_from_slice(&eth_tx_hash);
+        claim_data.extend_from_slice(&0u64.to_le_bytes());
+        assert_eq!(
+            process(&program_id, &mut accounts, &claim_data),
+            Err(LockerError::InsolventMint.into())
+        );
+        assert!(!Claim::unpack(&accounts[2].data).unwrap().claimed);
+    }
+
+    #[test]
+    fn test_mint_with_invalid_mint_account() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                total_locked: 1_000,
+                ..Locker::default()
+            }),
+            token_account(&mint, &Pubkey::new_unique(), 0),
+            TestAccount::new(mint, 0, vec![0u8; spl_token::state::Mint::LEN], spl_token::id()),
+            TestAccount::program(spl_token::id()),
+            processed_account(&program_id),
+            TestAccount::program(system_program::id()),
+            event_marker_account(&program_id, &[0u8; 32], 0),
+        ];
+        assert_eq!(
+            process(&program_id, &mut accounts, &mint_data(100, 0)),
+            Err(LockerError::InvalidMint.into())
+        );
+
+        accounts[3] = mint_account(mint, &Pubkey::new_unique());
+        assert_eq!(
+            process(&program_id, &mut accounts, &mint_data(100, 0)),
+            Err(LockerError::InvalidMint.into())
+        );
+
+        accounts[3] = mint_account(mint, &authority);
+        assert!(process(&program_id, &mut accounts, &mint_data(100, 0)).is_ok());
+    }
+
+    #[test]
+    fn test_max_mint_per_tx() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                total_locked: 1_000,
+                ..Locker::default()
+            }),
+        ];
+        let mut set_max_data = vec![19u8];
+        set_max_data.extend_from_slice(&100u64.to_le_bytes());
+        assert!(process(&program_id, &mut accounts, &set_max_data).is_ok());
+        assert!(apply_queued_change(&program_id, &mut accounts).is_ok());
+
+        accounts.extend(vec![
+            token_account(&mint, &Pubkey::new_unique(), 0),
+            mint_account(mint, &authority),
+            TestAccount::program(spl_token::id()),
+            processed_account(&program_id),
+            TestAccount::program(system_program::id()),
+            event_marker_account(&program_id, &[0u8; 32], 0),
+        ]);
+        assert!(process(&program_id, &mut accounts, &mint_data(100, 0)).is_ok());
+        assert_eq!(
+            process(&program_id, &mut accounts, &mint_data(101, 1)),
+            Err(LockerError::AmountAboveMaximum.into())
+        );
+        assert_eq!(Locker::unpack(&accounts[1].data).unwrap().total_minted, 100);
+    }
+
+    #[test]
+    fn test_events_start_with_tag() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        take_logged();
+
+        let mut accounts = vec![
+            TestAccount::signer(owner).with_lamports(1_000),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority: Pubkey::new_unique(),
+                ..Locker::default()
+            }),
+            mintlog_account(&program_id),
+            TestAccount::program(system_program::id()),
+        ];
+        assert!(process(&program_id, &mut accounts, &lock_and_mint_data(300)).is_ok());
+        let logged = take_logged();
+        assert_eq!(logged.len(), 2);
+        assert_eq!(logged[0][0], wire::EVENT_TAG);
+        assert_eq!(logged[0][2], accounts[2].data[state::MAGIC_LEN..]);
+        assert_eq!(logged[1][0], wire::RATIO_TAG);
+
+        let mut accounts = vec![
+            TestAccount::signer(owner),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority: Pubkey::new_unique(),
+                total_locked: 1_000,
+                total_minted: 1_000,
+                ..Locker::default()
+            }),
+            burnlog_account(&program_id),
+            token_account(&mint, &owner, 10),
+            TestAccount::new(mint, 0, vec![], spl_token::id()),
+            TestAccount::program(spl_token::id()),
+        ];
+        assert!(process(&program_id, &mut accounts, &burn_and_release_data(10)).is_ok());
+        let logged = take_logged();
+        assert_eq!(logged.len(), 2);
+        assert_eq!(logged[0][0], wire::EVENT_TAG);
+        assert_eq!(logged[0][2], accounts[2].data[state::MAGIC_LEN..]);
+        assert_eq!(logged[1][0], wire::RATIO_TAG);
+    }
+
+    #[test]
+    fn test_assert_solvent() {
+        assert!(Processor::assert_solvent(1_000, 1_000, 9, 9).is_ok());
+        assert!(Processor::assert_solvent(1, 1_000_000_000, 9, 18).is_ok());
+        assert!(Processor::assert_solvent(0, 0, 0, 38).is_ok());
+    }
+
+    #[test]
+    fn test_assert_solvent_insolvent() {
+        assert_eq!(
+            Processor::assert_solvent(999, 1_000, 9, 9),
+            Err(LockerError::InsolventMint.into())
+        );
+        assert_eq!(
+            Processor::assert_solvent(1, 1_000_000_001, 9, 18),
+            Err(LockerError::InsolventMint.into())
+        );
+    }
+
+    #[test]
+    fn test_assert_solvent_overflow() {
+        assert_eq!(
+            Processor::assert_solvent(1, 1, 0, 78),
+            Err(LockerError::UnexpectedDecimalConversion.into())
+        );
+        assert_eq!(
+            Processor::assert_solvent(u64::MAX, 1, 0, 60),
+            Err(LockerError::UnexpectedDecimalConversion.into())
+        );
+    }
+
+    #[test]
+    fn test_get_roles() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let backup_authority = Pubkey::new_unique();
+        let mut accounts = vec![
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                backup_authority,
+                ..Locker::default()
+            }),
+        ];
+        assert!(process(&program_id, &mut accounts, &[20]).is_ok());
+
+        let roles = RolesView::unpack_from_slice(&take_return_data().unwrap()).unwrap();
+        assert_eq!(roles, RolesView {
+            authority,
+            backup_authority,
+        });
+    }
+
+    #[test]
+    fn test_release_to_vault_rejected() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let (state_pubkey, _) = Pubkey::find_program_address(&[b"Locker", b"Init"], &program_id);
+        let vault = || state_account(&program_id, Locker {
+            is_initialized: true,
+            authority,
+            total_locked: 1_000,
+            ..Locker::default()
+        }).with_lamports(1_000);
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            vault(),
+            vault(),
+            TestAccount::program(system_program::id()),
+            release_record_account(&program_id, &state_pubkey),
+            processed_account(&program_id),
+        ];
+        assert_eq!(
+            process(&program_id, &mut accounts, &release_data(100, 0)),
+            Err(LockerError::InvalidDestination.into())
+        );
+        assert_eq!(accounts[1].lamports, 1_000);
+
+        let mut preview_data = vec![14u8];
+        preview_data.extend_from_slice(&100u64.to_le_bytes());
+        preview_data.extend_from_slice(state_pubkey.as_ref());
+        let mut accounts = vec![vault(), release_record_account(&program_id, &state_pubkey)];
+        assert!(process(&program_id, &mut accounts, &preview_data).is_ok());
+        assert_eq!(
+            take_return_data(),
+            Some(u64::from(ProgramError::from(LockerError::InvalidDestination)).to_le_bytes().to_vec())
+        );
+    }
+
+    #[test]
+    fn test_decimal_conversion_round_trips() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        // fixed seed so a failure reproduces, every assert names its inputs
+        const SEED: u64 = 0x5eed_0719;
+        let mut rng = StdRng::seed_from_u64(SEED);
+        for i in 0..10_000 {
+            let underlying_decimals: u8 = rng.gen_range(0, MAX_DECIMALS + 1);
+            // every tenth sample pins the equal decimals case
+            let spl_decimals = if i % 10 == 0 {
+                underlying_decimals
+            } else {
+                rng.gen_range(0, underlying_decimals + 1)
+            };
+            let spl_amount: u64 = rng.gen();
+            let inputs = format!(
+                "seed {:#x} sample {}: underlying_decimals {}, spl_decimals {}, spl_amount {}",
+                SEED, i, underlying_decimals, spl_decimals, spl_amount
+            );
+
+            // expanding never truncates, so spl -> underlying -> spl is exact
             let underlying_amount =
-                U256::from(spl_amount) * U256::exp10((underlying_decimals - spl_decimals) as usize);
-            return Ok(underlying_amount);
+                Processor::underlying_amount_from_spl_amount(underlying_decimals, spl_decimals, spl_amount).unwrap();
+            assert_eq!(
+                Processor::spl_amount_from_underlying_amount(
+                    underlying_decimals,
+                    spl_decimals,
+                    underlying_amount
+                ),
+                Ok(spl_amount),
+                "{}", inputs
+            );
+
+            // dust below one spl unit is truncated, never rounded up
+            let unit = U256::exp10((underlying_decimals - spl_decimals) as usize);
+            let dust = U256::from(rng.gen::<u64>()) % unit;
+            let spl_truncated = Processor::spl_amount_from_underlying_amount(
+                underlying_decimals,
+                spl_decimals,
+                underlying_amount + dust
+            ).unwrap();
+            assert_eq!(spl_truncated, spl_amount, "{}, dust {}", inputs, dust);
+            let round_trip =
+                Processor::underlying_amount_from_spl_amount(underlying_decimals, spl_decimals, spl_truncated).unwrap();
+            assert!(round_trip <= underlying_amount + dust, "{}, dust {}", inputs, dust);
+            assert_eq!(round_trip, underlying_amount, "{}, dust {}", inputs, dust);
         }
-        Err(LockerError::UnexpectedDecimalConversion.into())
+    }
+
+    #[test]
+    fn test_config_change_timelock() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        CLOCK.with(|clock| clock.borrow_mut().slot = 100);
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                ..Locker::default()
+            }),
+        ];
+        assert_eq!(
+            process(&program_id, &mut accounts, &[21]),
+            Err(LockerError::NoPendingConfigChange.into())
+        );
+
+        let mut set_cap_data = vec![7u8];
+        set_cap_data.extend_from_slice(&500u64.to_le_bytes());
+        assert!(process(&program_id, &mut accounts, &set_cap_data).is_ok());
+        let state_info = Locker::unpack(&accounts[1].data).unwrap();
+        assert_eq!(state_info.total_locked_cap, 0);
+        assert_eq!(state_info.pending_change, Some(ConfigChange::DepositCap(500)));
+        assert_eq!(state_info.pending_change_slot, 100 + state::CONFIG_TIMELOCK_SLOTS);
+
+        CLOCK.with(|clock| clock.borrow_mut().slot = 99 + state::CONFIG_TIMELOCK_SLOTS);
+        assert_eq!(
+            process(&program_id, &mut accounts, &[21]),
+            Err(LockerError::ConfigChangeLocked.into())
+        );
+
+        CLOCK.with(|clock| clock.borrow_mut().slot = 100 + state::CONFIG_TIMELOCK_SLOTS);
+        assert!(process(&program_id, &mut accounts, &[21]).is_ok());
+        let state_info = Locker::unpack(&accounts[1].data).unwrap();
+        assert_eq!(state_info.total_locked_cap, 500);
+        assert_eq!(state_info.pending_change, None);
+        assert_eq!(
+            process(&program_id, &mut accounts, &[21]),
+            Err(LockerError::NoPendingConfigChange.into())
+        );
+    }
+
+    #[test]
+    fn test_underfunded_initializer() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let rent = Rent::default();
+        let mut rent_data = rent.lamports_per_byte_year.to_le_bytes().to_vec();
+        rent_data.extend_from_slice(&rent.exemption_threshold.to_le_bytes());
+        rent_data.push(rent.burn_percent);
+        let pda = |seed: &[u8]| Pubkey::find_program_address(&[b"Locker", seed], &program_id).0;
+        let total_init_rent = rent.minimum_balance(state::STATESIZE) + 2 * rent.minimum_balance(state::LOGSIZE);
+
+        let mut data = vec![0u8];
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(total_init_rent - 1),
+            TestAccount::new(pda(b"Init"), 0, vec![], system_program::id()),
+            TestAccount::new(pda(b"Mint"), 0, vec![], system_program::id()),
+            TestAccount::new(pda(b"Burn"), 0, vec![], system_program::id()),
+            TestAccount::program(program_id),
+            TestAccount::program(system_program::id()),
+            TestAccount::new(solana_program::sysvar::rent::id(), 0, rent_data, solana_program::sysvar::id()),
+        ];
+        take_invoked();
+        assert_eq!(
+            process(&program_id, &mut accounts, &data),
+            Err(LockerError::InsufficientFundsForInit.into())
+        );
+        assert!(take_invoked().is_empty());
+    }
+
+    #[test]
+    fn test_second_initialize_rejected() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let rent = Rent::default();
+        let mut rent_data = rent.lamports_per_byte_year.to_le_bytes().to_vec();
+        rent_data.extend_from_slice(&rent.exemption_threshold.to_le_bytes());
+        rent_data.push(rent.burn_percent);
+        let total_init_rent = rent.minimum_balance(state::STATESIZE) + 2 * rent.minimum_balance(state::LOGSIZE);
+        let authority = Pubkey::new_unique();
+
+        let mut data = vec![0u8];
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(total_init_rent),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                total_locked: 1_000,
+                ..Locker::default()
+            }),
+            mintlog_account(&program_id),
+            burnlog_account(&program_id),
+            TestAccount::program(program_id),
+            TestAccount::program(system_program::id()),
+            TestAccount::new(solana_program::sysvar::rent::id(), 0, rent_data, solana_program::sysvar::id()),
+        ];
+        take_invoked();
+        assert_eq!(
+            process(&program_id, &mut accounts, &data),
+            Err(LockerError::AlreadyInitialized.into())
+        );
+        assert!(take_invoked().is_empty());
+        let state_info = Locker::unpack(&accounts[1].data).unwrap();
+        assert_eq!((state_info.authority, state_info.total_locked), (authority, 1_000));
+    }
+
+    #[test]
+    fn test_init_metadata_pointer() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let rent = Rent::default();
+        let mut rent_data = rent.lamports_per_byte_year.to_le_bytes().to_vec();
+        rent_data.extend_from_slice(&rent.exemption_threshold.to_le_bytes());
+        rent_data.push(rent.burn_percent);
+        let pda = |seed: &[u8]| Pubkey::find_program_address(&[b"Locker", seed], &program_id).0;
+        let total_init_rent = rent.minimum_balance(state::STATESIZE) + 2 * rent.minimum_balance(state::LOGSIZE);
+        let metadata = Pubkey::new_unique();
+
+        let mut data = vec![0u8];
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(Pubkey::default().as_ref());
+        data.extend_from_slice(metadata.as_ref());
+        let init_accounts = |metadata_owner: Pubkey| vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(total_init_rent),
+            TestAccount::new(pda(b"Init"), 0, vec![0; state::STATESIZE], program_id),
+            TestAccount::new(pda(b"Mint"), 0, vec![0; state::LOGSIZE], program_id),
+            TestAccount::new(pda(b"Burn"), 0, vec![0; state::LOGSIZE], program_id),
+            TestAccount::program(program_id),
+            TestAccount::program(system_program::id()),
+            TestAccount::new(solana_program::sysvar::rent::id(), 0, rent_data.clone(), solana_program::sysvar::id()),
+            TestAccount::new(metadata, 0, vec![], metadata_owner),
+        ];
+
+        let mut accounts = init_accounts(Pubkey::new_unique());
+        assert_eq!(
+            process(&program_id, &mut accounts, &data),
+            Err(LockerError::InvalidMetadataAccount.into())
+        );
+
+        let mut accounts = init_accounts(mpl_token_metadata::id());
+        assert!(process(&program_id, &mut accounts, &data).is_ok());
+        let state_info = Locker::unpack(&accounts[1].data).unwrap();
+        assert_eq!(state_info.metadata, metadata);
+        assert_eq!(state_info.underlying_decimals, 18);
+
+        take_return_data();
+        assert!(process(&program_id, &mut accounts[1..2], &[34]).is_ok());
+        assert_eq!(take_return_data().unwrap(), metadata.to_bytes().to_vec());
+
+        // a trailing byte sets the underlying decimals
+        data.push(6);
+        let mut accounts = init_accounts(mpl_token_metadata::id());
+        assert!(process(&program_id, &mut accounts, &data).is_ok());
+        assert_eq!(Locker::unpack(&accounts[1].data).unwrap().underlying_decimals, 6);
+        *data.last_mut().unwrap() = MAX_DECIMALS + 1;
+        let mut accounts = init_accounts(mpl_token_metadata::id());
+        assert_eq!(
+            process(&program_id, &mut accounts, &data),
+            Err(LockerError::InvalidDecimals.into())
+        );
+    }
+
+    #[test]
+    fn test_instruction_results() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let take_result = || InstructionResult::unpack_from_slice(&take_return_data().unwrap()).unwrap();
+        let result = |amount, total_locked, total_minted| InstructionResult {
+            success: true,
+            amount,
+            total_locked,
+            total_minted,
+        };
+
+        let mut accounts = vec![
+            TestAccount::signer(owner).with_lamports(1_000),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                ..Locker::default()
+            }),
+            mintlog_account(&program_id),
+            TestAccount::program(system_program::id()),
+        ];
+        assert!(process(&program_id, &mut accounts, &lock_and_mint_data(300)).is_ok());
+        assert_eq!(take_result(), result(300, 300, 0));
+
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            accounts.swap_remove(1),
+            token_account(&mint, &owner, 0),
+            mint_account(mint, &authority),
+            TestAccount::program(spl_token::id()),
+            processed_account(&program_id),
+            TestAccount::program(system_program::id()),
+            event_marker_account(&program_id, &[0u8; 32], 0),
+        ];
+        assert!(process(&program_id, &mut accounts, &mint_data(100, 0)).is_ok());
+        assert_eq!(take_result(), result(100, 300, 100));
+
+        let mut accounts = vec![
+            TestAccount::signer(owner),
+            accounts.swap_remove(1),
+            burnlog_account(&program_id),
+            token_account(&mint, &owner, 100),
+            TestAccount::new(mint, 0, vec![], spl_token::id()),
+            TestAccount::program(spl_token::id()),
+        ];
+        assert!(process(&program_id, &mut accounts, &burn_and_release_data(40)).is_ok());
+        assert_eq!(take_result(), result(40, 300, 60));
+
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            accounts.swap_remove(1),
+            TestAccount::new(destination, 0, vec![], system_program::id()),
+            TestAccount::program(system_program::id()),
+            release_record_account(&program_id, &destination),
+            processed_account(&program_id),
+        ];
+        assert!(process(&program_id, &mut accounts, &release_data(50, 0)).is_ok());
+        assert_eq!(take_result(), result(50, 250, 60));
+    }
+
+    #[test]
+    fn test_eth_contract() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let eth_contract = [0x5au8; ETH_ADDRESS_LEN];
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                ..Locker::default()
+            }),
+        ];
+        let mut set_data = vec![22u8];
+        set_data.extend_from_slice(&eth_contract);
+        assert!(process(&program_id, &mut accounts, &set_data).is_ok());
+        assert!(process(&program_id, &mut accounts[1..], &[23]).is_ok());
+        assert_eq!(take_return_data(), Some(vec![0u8; ETH_ADDRESS_LEN]));
+
+        assert!(apply_queued_change(&program_id, &mut accounts).is_ok());
+        assert!(process(&program_id, &mut accounts[1..], &[23]).is_ok());
+        assert_eq!(take_return_data(), Some(eth_contract.to_vec()));
+
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(1_000),
+            accounts.swap_remove(1),
+            mintlog_account(&program_id),
+            TestAccount::program(system_program::id()),
+        ];
+        take_logged();
+        assert!(process(&program_id, &mut accounts, &lock_and_mint_data(300)).is_ok());
+        assert!(take_logged().iter().all(|fields| fields[1] == eth_contract));
+    }
+
+    #[test]
+    fn test_burn_and_close_empty_account() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let token_account_key = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(owner),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority: Pubkey::new_unique(),
+                total_locked: 1_000,
+                total_minted: 1_000,
+                ..Locker::default()
+            }),
+            burnlog_account(&program_id),
+            TestAccount {
+                key: token_account_key,
+                ..token_account(&mint, &owner, 10)
+            },
+            TestAccount::new(mint, 0, vec![], spl_token::id()),
+            TestAccount::program(spl_token::id()),
+        ];
+        assert_eq!(
+            process(&program_id, &mut accounts, &burn_and_close_data(9)),
+            Err(LockerError::TokenAccountNotEmpty.into())
+        );
+
+        take_invoked();
+        assert!(process(&program_id, &mut accounts, &burn_and_close_data(10)).is_ok());
+        let close = take_invoked().pop().unwrap();
+        assert_eq!(
+            close,
+            spl_token::instruction::close_account(
+                &spl_token::id(),
+                &token_account_key,
+                &owner,
+                &owner,
+                &[&owner]
+            ).unwrap()
+        );
+
+        accounts[3] = token_account(&mint, &Pubkey::new_unique(), 10);
+        assert_eq!(
+            process(&program_id, &mut accounts, &burn_and_close_data(10)),
+            Err(LockerError::InvalidAuthority.into())
+        );
+    }
+
+    /// Starts a release of `total` to `destination`, returning the state and
+    /// pending release accounts.
+    fn start_release(
+        program_id: &Pubkey,
+        authority: &Pubkey,
+        destination: &Pubkey,
+        total: u64,
+    ) -> (TestAccount, TestAccount) {
+        let mut accounts = vec![
+            TestAccount::signer(*authority),
+            state_account(program_id, Locker {
+                is_initialized: true,
+                authority: *authority,
+                total_locked: 1_000,
+                ..Locker::default()
+            }).with_lamports(vault_reserve() + 1_000),
+            pending_release_account(program_id, 3),
+            processed_account(program_id),
+            TestAccount::program(system_program::id()),
+        ];
+        assert!(process(program_id, &mut accounts, &start_release_data(total, 3, destination)).is_ok());
+        assert_eq!(
+            process(program_id, &mut accounts, &start_release_data(total, 3, destination)),
+            Err(LockerError::AlreadyProcessed.into())
+        );
+        let pending_release = accounts.swap_remove(2);
+        (accounts.swap_remove(1), pending_release)
+    }
+
+    #[test]
+    fn test_release_filled_in_two_parts() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let (state_info, pending_release) = start_release(&program_id, &authority, &destination, 900);
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_info,
+            pending_release,
+            TestAccount::new(destination, 0, vec![], system_program::id()),
+            TestAccount::program(system_program::id()),
+            release_record_account(&program_id, &destination),
+        ];
+
+        assert!(process(&program_id, &mut accounts, &fill_release_data(3, 600)).is_ok());
+        assert!(process(&program_id, &mut accounts, &fill_release_data(3, 300)).is_ok());
+        let pending_release = PendingRelease::unpack(&accounts[2].data).unwrap();
+        assert_eq!(pending_release.filled, 900);
+        assert_eq!(pending_release.total, 900);
+        assert_eq!(accounts[3].lamports, 900);
+        assert_eq!(Locker::unpack(&accounts[1].data).unwrap().total_locked, 100);
+        assert_eq!(ReleaseRecord::unpack(&accounts[5].data).unwrap().total_released, 900);
+
+        assert_eq!(
+            process(&program_id, &mut accounts, &fill_release_data(3, 1)),
+            Err(LockerError::ReleaseOverfilled.into())
+        );
+    }
+
+    #[test]
+    fn test_release_overfill_rejected() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let (state_info, pending_release) = start_release(&program_id, &authority, &destination, 500);
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_info,
+            pending_release,
+            TestAccount::new(destination, 0, vec![], system_program::id()),
+            TestAccount::program(system_program::id()),
+            release_record_account(&program_id, &destination),
+        ];
+
+        assert_eq!(
+            process(&program_id, &mut accounts, &fill_release_data(3, 501)),
+            Err(LockerError::ReleaseOverfilled.into())
+        );
+        assert_eq!(accounts[3].lamports, 0);
+        assert_eq!(PendingRelease::unpack(&accounts[2].data).unwrap().filled, 0);
+
+        accounts[3] = TestAccount::new(Pubkey::new_unique(), 0, vec![], system_program::id());
+        assert_eq!(
+            process(&program_id, &mut accounts, &fill_release_data(3, 500)),
+            Err(LockerError::InvalidDestination.into())
+        );
+    }
+
+    #[test]
+    fn test_mint_solvency_scaled_by_decimals() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        // 1_000 lamports back 1 unit of a 6 decimal token, not 100
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                total_locked: 1_000,
+                ..Locker::default()
+            }),
+            token_account(&mint, &Pubkey::new_unique(), 0),
+            mint_account_with_decimals(mint, &authority, 6),
+            TestAccount::program(spl_token::id()),
+            processed_account(&program_id),
+            TestAccount::program(system_program::id()),
+            event_marker_account(&program_id, &[0u8; 32], 0),
+        ];
+        assert_eq!(
+            process(&program_id, &mut accounts, &mint_data(100, 0)),
+            Err(LockerError::InsolventMint.into())
+        );
+        assert!(process(&program_id, &mut accounts, &mint_data(1, 0)).is_ok());
+        // the cached ratio scales the same way, fully backed reads as 100%
+        let state_info = Locker::unpack(&accounts[1].data).unwrap();
+        assert_eq!(state_info.mint_decimals, 6);
+        assert_eq!(state_info.collateralization_ratio, 10_000);
+        assert_eq!(
+            process(&program_id, &mut accounts, &mint_data(1, 1)),
+            Err(LockerError::InsolventMint.into())
+        );
+    }
+
+    fn claim_release_data(amount: u64, burn_id: u64, proof: &[[u8; 32]]) -> Vec<u8> {
+        let mut data = vec![28u8];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.extend_from_slice(&burn_id.to_le_bytes());
+        for node in proof {
+            data.extend_from_slice(node);
+        }
+        data
+    }
+
+    #[test]
+    fn test_claim_release_with_proof() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let recipients = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let leaves = [
+            Processor::release_leaf(&recipients[0], 100, 0),
+            Processor::release_leaf(&recipients[1], 200, 1),
+            Processor::release_leaf(&recipients[2], 300, 2),
+            Processor::release_leaf(&recipients[2], 400, 3),
+        ];
+        let left = Processor::merkle_parent(&leaves[0], &leaves[1]);
+        let right = Processor::merkle_parent(&leaves[2], &leaves[3]);
+        let root = Processor::merkle_parent(&left, &right);
+
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                total_locked: 1_000,
+                ..Locker::default()
+            }).with_lamports(vault_reserve() + 1_000),
+        ];
+        let mut root_data = vec![27u8];
+        root_data.extend_from_slice(&root);
+        assert!(process(&program_id, &mut accounts, &root_data).is_ok());
+
+        // anyone may relay the proof, the recipient does not sign
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()),
+            accounts.swap_remove(1),
+            TestAccount::new(recipients[1], 0, vec![], system_program::id()),
+            TestAccount::program(system_program::id()),
+            release_record_account(&program_id, &recipients[1]),
+            processed_account(&program_id),
+        ];
+        let proof = [leaves[0], right];
+        assert_eq!(
+            process(&program_id, &mut accounts, &claim_release_data(201, 1, &proof)),
+            Err(LockerError::InvalidMerkleProof.into())
+        );
+        assert_eq!(
+            process(&program_id, &mut accounts, &claim_release_data(200, 1, &[leaves[1], right])),
+            Err(LockerError::InvalidMerkleProof.into())
+        );
+        assert!(process(&program_id, &mut accounts, &claim_release_data(200, 1, &proof)).is_ok());
+        assert_eq!(accounts[2].lamports, 200);
+        assert_eq!(Locker::unpack(&accounts[1].data).unwrap().total_locked, 800);
+
+        assert_eq!(
+            process(&program_id, &mut accounts, &claim_release_data(200, 1, &proof)),
+            Err(LockerError::AlreadyProcessed.into())
+        );
+        assert_eq!(accounts[2].lamports, 200);
+    }
+
+    #[test]
+    fn test_expected_decimals() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                total_locked: 1_000_000,
+                mint_decimals: 6,
+                ..Locker::default()
+            }),
+            token_account(&mint, &authority, 0),
+            mint_account_with_decimals(mint, &authority, 9),
+            TestAccount::program(spl_token::id()),
+            processed_account(&program_id),
+            TestAccount::program(system_program::id()),
+            event_marker_account(&program_id, &[0u8; 32], 0),
+        ];
+        // the stored decimals are checked, not whatever the mint account says
+        let mut data = mint_data(1, 0);
+        data.push(9);
+        assert_eq!(
+            process(&program_id, &mut accounts, &data),
+            Err(LockerError::InvalidDecimals.into())
+        );
+        assert_eq!(Locker::unpack(&accounts[1].data).unwrap().mint_decimals, 6);
+
+        accounts[3] = mint_account_with_decimals(mint, &authority, 6);
+        assert_eq!(
+            process(&program_id, &mut accounts, &data),
+            Err(LockerError::InvalidDecimals.into())
+        );
+        *data.last_mut().unwrap() = 6;
+        assert!(process(&program_id, &mut accounts, &data).is_ok());
+
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            accounts.swap_remove(1),
+            burnlog_account(&program_id),
+            token_account(&mint, &authority, 1),
+            mint_account_with_decimals(mint, &authority, 6),
+            TestAccount::program(spl_token::id()),
+        ];
+        let mut data = burn_and_release_data(1);
+        data.push(9);
+        assert_eq!(
+            process(&program_id, &mut accounts, &data),
+            Err(LockerError::InvalidDecimals.into())
+        );
+        *data.last_mut().unwrap() = 6;
+        assert!(process(&program_id, &mut accounts, &data).is_ok());
+        assert_eq!(Locker::unpack(&accounts[1].data).unwrap().total_minted, 0);
+    }
+
+    fn recipient_record_accounts(
+        program_id: &Pubkey,
+        signer: TestAccount,
+        authority: &Pubkey,
+        recipient: &Pubkey,
+        last_release_slot: u64
+    ) -> Vec<TestAccount> {
+        let mut release_record = release_record_account(program_id, recipient).with_lamports(500);
+        ReleaseRecord::pack(ReleaseRecord {
+            is_initialized: true,
+            destination: *recipient,
+            total_released: 1_000,
+            last_release_slot,
+        }, &mut release_record.data).unwrap();
+        vec![
+            signer,
+            state_account(program_id, Locker {
+                is_initialized: true,
+                authority: *authority,
+                ..Locker::default()
+            }),
+            TestAccount::new(*recipient, 0, vec![], system_program::id()),
+            release_record,
+        ]
+    }
+
+    #[test]
+    fn test_recipient_closes_release_record() {
+        setup_syscall_stubs();
+        CLOCK.with(|clock| clock.borrow_mut().slot = 100);
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+
+        let mut accounts = recipient_record_accounts(
+            &program_id,
+            TestAccount::signer(Pubkey::new_unique()),
+            &authority,
+            &recipient,
+            100
+        );
+        assert_eq!(
+            process(&program_id, &mut accounts, &[29]),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        // the recipient closes in the slot of the last release, no waiting
+        accounts[0] = TestAccount::signer(recipient);
+        assert!(process(&program_id, &mut accounts, &[29]).is_ok());
+        assert_eq!(accounts[0].lamports, 500);
+        assert_eq!(accounts[3].lamports, 0);
+        assert!(accounts[3].data.iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn test_authority_closes_idle_release_record() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+
+        let mut accounts = recipient_record_accounts(
+            &program_id,
+            TestAccount::signer(authority),
+            &authority,
+            &recipient,
+            100
+        );
+        CLOCK.with(|clock| clock.borrow_mut().slot = 99 + state::RELEASE_RECORD_INACTIVITY_SLOTS);
+        assert_eq!(
+            process(&program_id, &mut accounts, &[29]),
+            Err(LockerError::ReleaseRecordActive.into())
+        );
+        assert_eq!(accounts[3].lamports, 500);
+
+        CLOCK.with(|clock| clock.borrow_mut().slot = 100 + state::RELEASE_RECORD_INACTIVITY_SLOTS);
+        assert!(process(&program_id, &mut accounts, &[29]).is_ok());
+        assert_eq!(accounts[0].lamports, 500);
+        assert_eq!(accounts[3].lamports, 0);
+    }
+
+    #[test]
+    fn test_release_limited_by_vault_balance() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        // the counter claims far more than the vault actually holds
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                total_locked: 1_000_000,
+                ..Locker::default()
+            }).with_lamports(vault_reserve() + 100),
+            TestAccount::new(destination, 0, vec![], system_program::id()),
+            TestAccount::program(system_program::id()),
+            release_record_account(&program_id, &destination),
+            processed_account(&program_id),
+        ];
+        assert_eq!(
+            process(&program_id, &mut accounts, &release_data(101, 0)),
+            Err(LockerError::InsufficientLiquidity.into())
+        );
+        assert_eq!(accounts[1].lamports, vault_reserve() + 100);
+
+        assert!(process(&program_id, &mut accounts, &release_data(100, 0)).is_ok());
+        assert_eq!(accounts[1].lamports, vault_reserve());
+        assert_eq!(accounts[2].lamports, 100);
+    }
+
+    #[test]
+    fn test_token_errors_mapped() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(owner),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority: Pubkey::new_unique(),
+                total_locked: 1_000,
+                total_minted: 1_000,
+                ..Locker::default()
+            }),
+            burnlog_account(&program_id),
+            token_account(&mint, &owner, 10),
+            TestAccount::new(mint, 0, vec![], spl_token::id()),
+            TestAccount::program(spl_token::id()),
+        ];
+        fail_next_cpi(TokenError::AccountFrozen.into());
+        assert_eq!(
+            process(&program_id, &mut accounts, &burn_and_release_data(10)),
+            Err(LockerError::TokenAccountFrozen.into())
+        );
+
+        fail_next_cpi(TokenError::OwnerMismatch.into());
+        assert_eq!(
+            process(&program_id, &mut accounts, &burn_and_release_data(10)),
+            Err(LockerError::TokenOwnerMismatch.into())
+        );
+
+        // errors with nothing to act on pass through untouched
+        fail_next_cpi(TokenError::MintMismatch.into());
+        assert_eq!(
+            process(&program_id, &mut accounts, &burn_and_release_data(10)),
+            Err(TokenError::MintMismatch.into())
+        );
+        assert!(process(&program_id, &mut accounts, &burn_and_release_data(10)).is_ok());
+    }
+
+    #[test]
+    fn test_burn_checks_pinned_mint() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let other_mint = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(owner),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority: Pubkey::new_unique(),
+                total_locked: 1_000,
+                total_minted: 1_000,
+                mint,
+                ..Locker::default()
+            }),
+            burnlog_account(&program_id),
+            token_account(&other_mint, &owner, 10),
+            TestAccount::new(other_mint, 0, vec![], spl_token::id()),
+            TestAccount::program(spl_token::id()),
+        ];
+        assert_eq!(
+            process(&program_id, &mut accounts, &burn_and_release_data(10)),
+            Err(LockerError::InvalidMint.into())
+        );
+        assert_eq!(Locker::unpack(&accounts[1].data).unwrap().total_minted, 1_000);
+
+        accounts[3] = token_account(&mint, &owner, 10);
+        accounts[4] = TestAccount::new(mint, 0, vec![], spl_token::id());
+        assert!(process(&program_id, &mut accounts, &burn_and_release_data(10)).is_ok());
+        assert_eq!(Locker::unpack(&accounts[1].data).unwrap().total_minted, 990);
+    }
+
+    #[test]
+    fn test_local_mode_lock_mints() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                ..Locker::default()
+            }),
+        ];
+        assert!(process(&program_id, &mut accounts, &[30, 1]).is_ok());
+        assert!(!Locker::unpack(&accounts[1].data).unwrap().local_mode);
+        assert!(apply_queued_change(&program_id, &mut accounts).is_ok());
+        assert!(Locker::unpack(&accounts[1].data).unwrap().local_mode);
+
+        let state_key = accounts[1].key;
+        let mut accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()).with_lamports(1_000),
+            accounts.swap_remove(1),
+            mintlog_account(&program_id),
+            TestAccount::program(system_program::id()),
+            token_account(&mint, &Pubkey::new_unique(), 0),
+            mint_account_with_decimals(mint, &state_key, 6),
+            TestAccount::program(spl_token::id()),
+        ];
+        // a lock cannot pick the mint, the authority has to pin it first
+        assert_eq!(
+            process(&program_id, &mut accounts, &lock_and_mint_data(400)),
+            Err(LockerError::InvalidMint.into())
+        );
+        let mut state_info = Locker::unpack(&accounts[1].data).unwrap();
+        state_info.mint = mint;
+        Locker::pack(state_info, &mut accounts[1].data).unwrap();
+        assert_eq!(
+            process(&program_id, &mut accounts, &lock_and_mint_data(400)),
+            Err(LockerError::InvalidDecimals.into())
+        );
+
+        accounts[5] = mint_account(mint, &state_key);
+        take_invoked();
+        take_logged();
+        let mintlog_before = accounts[2].data.clone();
+        assert!(process(&program_id, &mut accounts, &lock_and_mint_data(400)).is_ok());
+        let state_info = Locker::unpack(&accounts[1].data).unwrap();
+        assert_eq!(state_info.total_locked, 400);
+        assert_eq!(state_info.total_minted, 400);
+        assert_eq!(state_info.outbound_nonce, 0);
+        assert!(!state_info.in_progress);
+        assert_eq!(accounts[1].lamports, 400);
+
+        let invoked = take_invoked();
+        assert_eq!(invoked.len(), 2);
+        assert_eq!(invoked[1].program_id, spl_token::id());
+        assert_eq!(
+            invoked[1].data,
+            spl_token::instruction::TokenInstruction::MintTo { amount: 400 }.pack()
+        );
+        // nothing reaches the other side, or it would be backed twice
+        let logged = take_logged();
+        assert_eq!(logged.len(), 1);
+        assert_eq!(logged[0][0], wire::RATIO_TAG);
+        assert_eq!(accounts[2].data, mintlog_before);
+    }
+
+    #[test]
+    fn test_dump_state() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut accounts = vec![state_account(&program_id, Locker {
+            is_initialized: true,
+            authority: Pubkey::new_unique(),
+            total_locked: 1_000,
+            eth_contract: [9u8; ETH_ADDRESS_LEN],
+            local_mode: true,
+            ..Locker::default()
+        })];
+        assert!(process(&program_id, &mut accounts, &[31]).is_ok());
+        let dumped = take_return_data().unwrap();
+        assert_eq!(dumped.len(), state::STATESIZE);
+        assert_eq!(dumped, accounts[0].data);
+        assert_eq!(Locker::unpack(&dumped).unwrap().total_locked, 1_000);
+
+        accounts[0].data.push(0);
+        assert_eq!(
+            process(&program_id, &mut accounts, &[31]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_ratio_event_after_mint() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                total_locked: 1_000,
+                total_minted: 200,
+                ..Locker::default()
+            }),
+            token_account(&mint, &Pubkey::new_unique(), 0),
+            mint_account(mint, &authority),
+            TestAccount::program(spl_token::id()),
+            processed_account(&program_id),
+            TestAccount::program(system_program::id()),
+            event_marker_account(&program_id, &[0u8; 32], 0),
+        ];
+        take_logged();
+        assert!(process(&program_id, &mut accounts, &mint_data(300, 0)).is_ok());
+
+        let logged = take_logged();
+        let ratio_events: Vec<_> = logged.iter().filter(|fields| fields[0] == wire::RATIO_TAG).collect();
+        assert_eq!(ratio_events.len(), 1);
+        // 1_000 locked over 500 minted
+        assert_eq!(ratio_events[0][2], 20_000u64.to_le_bytes());
+        assert_eq!(
+            Locker::unpack(&accounts[1].data).unwrap().collateralization_ratio,
+            20_000
+        );
+    }
+
+    fn update_config_data(
+        epoch_mint_cap: Option<u64>,
+        deposit_cap: Option<u64>,
+        large_release_threshold: Option<u64>,
+        max_mint_per_tx: Option<u64>
+    ) -> Vec<u8> {
+        let mut data = vec![33u8, 0u8];
+        for (index, field) in [epoch_mint_cap, deposit_cap, large_release_threshold, max_mint_per_tx].iter().enumerate() {
+            if field.is_some() {
+                data[1] |= 1 << index;
+            }
+            data.extend_from_slice(&field.unwrap_or_default().to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_update_config_atomically() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                epoch_mint_cap: 1_000,
+                total_locked_cap: 5_000,
+                large_release_threshold: 100,
+                max_mint_per_tx: 500,
+                ..Locker::default()
+            }),
+        ];
+        // raising the per-mint maximum alone would clash with the old epoch cap
+        assert!(process(&program_id, &mut accounts, &update_config_data(Some(2_000), None, Some(200), Some(1_500))).is_ok());
+        let state_info = Locker::unpack(&accounts[1].data).unwrap();
+        assert_eq!(state_info.max_mint_per_tx, 500);
+        assert!(apply_queued_change(&program_id, &mut accounts).is_ok());
+
+        let state_info = Locker::unpack(&accounts[1].data).unwrap();
+        assert_eq!(state_info.epoch_mint_cap, 2_000);
+        assert_eq!(state_info.total_locked_cap, 5_000);
+        assert_eq!(state_info.large_release_threshold, 200);
+        assert_eq!(state_info.max_mint_per_tx, 1_500);
+        assert_eq!(state_info.pending_change, None);
+    }
+
+    #[test]
+    fn test_update_config_rejects_inconsistent_limits() {
+        setup_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(authority),
+            state_account(&program_id, Locker {
+                is_initialized: true,
+                authority,
+                epoch_mint_cap: 1_000,
+                total_locked_cap: 5_000,
+                ..Locker::default()
+            }),
+        ];
+        assert_eq!(
+            process(&program_id, &mut accounts, &update_config_data(None, None, None, Some(1_001))),
+            Err(LockerError::MaxMintAboveEpochCap.into())
+        );
+        assert_eq!(
+            process(&program_id, &mut accounts, &update_config_data(None, Some(4_000), Some(4_001), None)),
+            Err(LockerError::ThresholdAboveDepositCap.into())
+        );
+        // lifting the cap makes the same threshold fine
+        assert!(process(&program_id, &mut accounts, &update_config_data(None, Some(0), Some(4_001), None)).is_ok());
+        assert_eq!(
+            process(&program_id, &mut accounts, &[33, 1 << 4]),
+            Err(LockerError::InvalidInstruction.into())
+        );
     }
 }
@@ -1,23 +1,377 @@
 use solana_program::{
+    clock::{Epoch, Slot},
     program_pack::{IsInitialized, Pack, Sealed},
     program_error::ProgramError,
     pubkey::Pubkey,
 };
 use spl_math::uint::U256;
-use crate::types::DESTINATION_CHAIN_ADDRESS_LEN;
+use crate::error::LockerError;
+use crate::types::{DEFAULT_UNDERLYING_DECIMALS, DESTINATION_CHAIN_ADDRESS_LEN, ETH_ADDRESS_LEN};
+use crate::wire;
 
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
 
-pub const STATESIZE: usize = 49usize;
-pub const LOGSIZE: usize = 32 + DESTINATION_CHAIN_ADDRESS_LEN;
+/// Leads every `Locker` and log account, so an account belonging to another
+/// program or an older layout is rejected instead of misread.
+pub const MAGIC: [u8; 4] = *b"LKR1";
+pub const MAGIC_LEN: usize = 4usize;
 
+pub const STATESIZE: usize = 310usize;
+/// Size of the state account under the original layout, holding only the
+/// initialized flag, the authority and both totals
+pub const LEGACY_STATESIZE: usize = 49usize;
+pub const LOG_AMOUNT_LEN: usize = wire::AMOUNT_LEN;
+pub const LOGSIZE: usize = MAGIC_LEN + wire::MESSAGE_LEN;
+
+// `to_big_endian` fills the whole slot, so a slot that is not exactly a
+// U256 would silently truncate or misplace the amount
+const _: () = assert!(LOG_AMOUNT_LEN == std::mem::size_of::<U256>());
+pub const RELEASE_RECORD_SIZE: usize = 49usize;
+pub const CLAIM_SIZE: usize = 90usize;
+pub const PENDING_RELEASE_SIZE: usize = 57usize;
+pub const RECEIPTSIZE: usize = 8 + 32 + DESTINATION_CHAIN_ADDRESS_LEN + 8 + 8 + 8;
+pub const MINT_RECEIPT_SIZE: usize = 8 + 8 + 32 + 32 + 8 + 8;
+pub const HEALTH_SUMMARY_SIZE: usize = 8 + 8 + 8 + 32 + 8 + 1;
+pub const ROLES_VIEW_SIZE: usize = 32 + 32;
+pub const INSTRUCTION_RESULT_SIZE: usize = 1 + 8 + 8 + 8;
+
+/// Slots a queued config change waits before it can be applied, roughly a
+/// day at 400ms slots, so users have time to exit if they disagree
+pub const CONFIG_TIMELOCK_SLOTS: u64 = 216_000u64;
+
+/// Slots without a release after which the authority may close a
+/// recipient's release record, roughly 90 days
+pub const RELEASE_RECORD_INACTIVITY_SLOTS: u64 = 90 * CONFIG_TIMELOCK_SLOTS;
+
+/// Every limit at once, queued by `UpdateConfig` and applied as a whole so
+/// the limits never disagree halfway through an update
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ConfigLimits {
+    pub epoch_mint_cap: u64,
+    pub deposit_cap: u64,
+    pub large_release_threshold: u64,
+    pub max_mint_per_tx: u64,
+}
+
+impl ConfigLimits {
+    /// Rejects limits that contradict each other. 0 disables a limit, so
+    /// only limits that are set are compared.
+    pub fn validate(&self) -> Result<(), ProgramError> {
+        // a single mint above the epoch cap could never go through
+        if self.epoch_mint_cap != 0 && self.max_mint_per_tx > self.epoch_mint_cap {
+            return Err(LockerError::MaxMintAboveEpochCap.into());
+        }
+        // nothing above the deposit cap is ever locked, so could never be released
+        if self.deposit_cap != 0 && self.large_release_threshold > self.deposit_cap {
+            return Err(LockerError::ThresholdAboveDepositCap.into());
+        }
+        Ok(())
+    }
+}
+
+/// A config-mutating instruction, held in `Locker::pending_change` until
+/// the timelock runs out
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConfigChange {
+    EpochMintCap(u64),
+    DepositCap(u64),
+    BackupAuthority(Pubkey),
+    LargeReleaseThreshold(u64),
+    MaxMintPerTx(u64),
+    EthContract([u8; ETH_ADDRESS_LEN]),
+    LocalMode(bool),
+    Limits(ConfigLimits),
+}
+
+impl ConfigChange {
+    fn unpack(kind: &[u8; 1], value: &[u8; 32]) -> Result<Option<Self>, ProgramError> {
+        let amount = u64::from_le_bytes(*array_ref![value, 0, 8]);
+        Ok(match kind {
+            [0] => None,
+            [1] => Some(ConfigChange::EpochMintCap(amount)),
+            [2] => Some(ConfigChange::DepositCap(amount)),
+            [3] => Some(ConfigChange::BackupAuthority(Pubkey::new_from_array(*value))),
+            [4] => Some(ConfigChange::LargeReleaseThreshold(amount)),
+            [5] => Some(ConfigChange::MaxMintPerTx(amount)),
+            [6] => Some(ConfigChange::EthContract(*array_ref![value, 0, ETH_ADDRESS_LEN])),
+            [7] => Some(ConfigChange::LocalMode(amount != 0)),
+            [9] => {
+                let (
+                    epoch_mint_cap,
+                    deposit_cap,
+                    large_release_threshold,
+                    max_mint_per_tx,
+                ) = array_refs![value, 8, 8, 8, 8];
+                Some(ConfigChange::Limits(ConfigLimits {
+                    epoch_mint_cap: u64::from_le_bytes(*epoch_mint_cap),
+                    deposit_cap: u64::from_le_bytes(*deposit_cap),
+                    large_release_threshold: u64::from_le_bytes(*large_release_threshold),
+                    max_mint_per_tx: u64::from_le_bytes(*max_mint_per_tx),
+                }))
+            }
+            _ => return Err(ProgramError::InvalidAccountData),
+        })
+    }
+
+    fn pack(change: &Option<Self>, kind_dst: &mut [u8; 1], value_dst: &mut [u8; 32]) {
+        *value_dst = [0u8; 32];
+        let (kind, amount) = match change {
+            None => (0, 0),
+            Some(ConfigChange::EpochMintCap(cap)) => (1, *cap),
+            Some(ConfigChange::DepositCap(cap)) => (2, *cap),
+            Some(ConfigChange::BackupAuthority(backup_authority)) => {
+                kind_dst[0] = 3;
+                value_dst.copy_from_slice(backup_authority.as_ref());
+                return;
+            }
+            Some(ConfigChange::LargeReleaseThreshold(threshold)) => (4, *threshold),
+            Some(ConfigChange::MaxMintPerTx(max)) => (5, *max),
+            Some(ConfigChange::LocalMode(enabled)) => (7, *enabled as u64),
+            Some(ConfigChange::Limits(limits)) => {
+                kind_dst[0] = 9;
+                let (
+                    epoch_mint_cap_dst,
+                    deposit_cap_dst,
+                    large_release_threshold_dst,
+                    max_mint_per_tx_dst,
+                ) = mut_array_refs![value_dst, 8, 8, 8, 8];
+                *epoch_mint_cap_dst = limits.epoch_mint_cap.to_le_bytes();
+                *deposit_cap_dst = limits.deposit_cap.to_le_bytes();
+                *large_release_threshold_dst = limits.large_release_threshold.to_le_bytes();
+                *max_mint_per_tx_dst = limits.max_mint_per_tx.to_le_bytes();
+                return;
+            }
+            Some(ConfigChange::EthContract(eth_contract)) => {
+                kind_dst[0] = 6;
+                value_dst[..ETH_ADDRESS_LEN].copy_from_slice(eth_contract);
+                return;
+            }
+        };
+        kind_dst[0] = kind;
+        value_dst[..8].copy_from_slice(&amount.to_le_bytes());
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Locker {
     pub is_initialized: bool,
     pub authority: Pubkey,
     pub total_locked: u64,
     pub total_minted: u64,
+    /// Maximum amount that can be minted per epoch, 0 means no cap
+    pub epoch_mint_cap: u64,
+    pub current_epoch: Epoch,
+    pub epoch_minted: u64,
+    /// Maximum value of total_locked, 0 means no cap
+    pub total_locked_cap: u64,
+    /// Set while a state-mutating instruction has CPIs outstanding
+    pub in_progress: bool,
+    /// total_locked over total_minted in basis points, u64::MAX while nothing is minted
+    pub collateralization_ratio: u64,
+    /// Key allowed the same privileged actions as `authority`, except
+    /// changing the backup itself. The default key means none is set.
+    pub backup_authority: Pubkey,
+    /// Releases above this amount need both the authority and the backup
+    /// authority to sign, 0 means no threshold
+    pub large_release_threshold: u64,
+    /// Maximum amount a single mint can issue, 0 means no cap
+    pub max_mint_per_tx: u64,
+    /// Config change queued behind the timelock, at most one at a time
+    pub pending_change: Option<ConfigChange>,
+    /// First slot at which `pending_change` can be applied
+    pub pending_change_slot: u64,
+    /// Address of the bridge contract on Ethereum, stamped into every event
+    pub eth_contract: [u8; ETH_ADDRESS_LEN],
+    /// Merkle root over a batch of releases claimable with a proof
+    pub release_root: [u8; 32],
+    /// Lock mints the wrapped token straight away to a local token account
+    /// instead of logging it for the relayer, for same-cluster testing and
+    /// SPL-to-SPL deployments. Never set on a cross-chain locker.
+    pub local_mode: bool,
+    /// Metaplex metadata account of the wrapped mint, the default key means
+    /// none is set
+    pub metadata: Pubkey,
+    /// Nonce the next lock or burn stamps into its log entry, shared by
+    /// both directions and only ever incremented
+    pub outbound_nonce: u64,
+    /// Decimals of the wrapped mint, recorded whenever a mint goes through
+    /// so the ratio can be kept without the mint account at hand
+    pub mint_decimals: u8,
+    /// Decimals of the token on Ethereum, which lock and burn events are
+    /// denominated in
+    pub underlying_decimals: u8,
+    /// Wrapped mint the locker answers for, pinned by the first mint or
+    /// `ClaimMintAuthority`. The default key means none is pinned yet.
+    pub mint: Pubkey,
+}
+
+/// Everything zeroed, except the decimals a fresh `Initialize` stores
+impl Default for Locker {
+    fn default() -> Self {
+        Locker {
+            is_initialized: false,
+            authority: Pubkey::default(),
+            total_locked: 0,
+            total_minted: 0,
+            epoch_mint_cap: 0,
+            current_epoch: 0,
+            epoch_minted: 0,
+            total_locked_cap: 0,
+            in_progress: false,
+            collateralization_ratio: 0,
+            backup_authority: Pubkey::default(),
+            large_release_threshold: 0,
+            max_mint_per_tx: 0,
+            pending_change: None,
+            pending_change_slot: 0,
+            eth_contract: [0u8; ETH_ADDRESS_LEN],
+            release_root: [0u8; 32],
+            local_mode: false,
+            metadata: Pubkey::default(),
+            outbound_nonce: 0,
+            mint_decimals: spl_token::native_mint::DECIMALS,
+            underlying_decimals: DEFAULT_UNDERLYING_DECIMALS,
+            mint: Pubkey::default(),
+        }
+    }
+}
+
+impl Locker {
+    /// Reads a state account still in the `LEGACY_STATESIZE` layout. Every
+    /// field the old layout lacks starts out as `Initialize` leaves it.
+    pub fn unpack_legacy(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() != LEGACY_STATESIZE {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let src = array_ref![src, 0, LEGACY_STATESIZE];
+        let (is_initialized, authority, total_locked, total_minted) = array_refs![src, 1, 32, 8, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let mut locker = Locker {
+            is_initialized,
+            authority: Pubkey::new_from_array(*authority),
+            total_locked: u64::from_le_bytes(*total_locked),
+            total_minted: u64::from_le_bytes(*total_minted),
+            ..Locker::default()
+        };
+        locker.update_collateralization_ratio()?;
+        Ok(locker)
+    }
+
+    /// `total_locked` over `total_minted` in basis points, u64::MAX while
+    /// nothing is minted. Lamports are locked and units of the wrapped mint
+    /// are minted, so both go through `scale_totals` before dividing.
+    pub fn compute_collateralization_ratio(&self) -> Result<u64, ProgramError> {
+        if self.total_minted == 0 {
+            return Ok(u64::MAX);
+        }
+        let (locked, minted) = scale_totals(
+            self.total_locked,
+            spl_token::native_mint::DECIMALS,
+            self.total_minted,
+            self.mint_decimals,
+        )?;
+        let ratio = locked
+            .checked_mul(U256::from(10_000u64))
+            .ok_or(LockerError::ArithmeticOverflow)?
+            / minted;
+        Ok(if ratio > U256::from(u64::MAX) { u64::MAX } else { ratio.as_u64() })
+    }
+
+    /// Recomputes the cached collateralization ratio.
+    pub fn update_collateralization_ratio(&mut self) -> Result<(), ProgramError> {
+        self.collateralization_ratio = self.compute_collateralization_ratio()?;
+        Ok(())
+    }
+
+    /// Applies a config change that has waited out the timelock.
+    pub fn apply_config_change(&mut self, change: ConfigChange) {
+        match change {
+            ConfigChange::EpochMintCap(cap) => self.epoch_mint_cap = cap,
+            ConfigChange::DepositCap(cap) => self.total_locked_cap = cap,
+            ConfigChange::BackupAuthority(backup_authority) => self.backup_authority = backup_authority,
+            ConfigChange::LargeReleaseThreshold(threshold) => self.large_release_threshold = threshold,
+            ConfigChange::MaxMintPerTx(max) => self.max_mint_per_tx = max,
+            ConfigChange::EthContract(eth_contract) => self.eth_contract = eth_contract,
+            ConfigChange::LocalMode(enabled) => self.local_mode = enabled,
+            ConfigChange::Limits(limits) => {
+                self.epoch_mint_cap = limits.epoch_mint_cap;
+                self.total_locked_cap = limits.deposit_cap;
+                self.large_release_threshold = limits.large_release_threshold;
+                self.max_mint_per_tx = limits.max_mint_per_tx;
+            }
+        }
+    }
+
+    /// The limits currently in force.
+    pub fn config_limits(&self) -> ConfigLimits {
+        ConfigLimits {
+            epoch_mint_cap: self.epoch_mint_cap,
+            deposit_cap: self.total_locked_cap,
+            large_release_threshold: self.large_release_threshold,
+            max_mint_per_tx: self.max_mint_per_tx,
+        }
+    }
+
+    /// Takes the nonce for an outbound event and advances the counter.
+    pub fn next_outbound_nonce(&mut self) -> Result<u64, ProgramError> {
+        let nonce = self.outbound_nonce;
+        self.outbound_nonce = nonce
+            .checked_add(1)
+            .ok_or(LockerError::ArithmeticOverflow)?;
+        Ok(nonce)
+    }
+
+    /// Pins `mint` as the wrapped mint if none is pinned yet, and rejects
+    /// any other mint once one is.
+    pub fn pin_mint(&mut self, mint: &Pubkey) -> Result<(), ProgramError> {
+        if self.mint == Pubkey::default() {
+            self.mint = *mint;
+        }
+        self.check_mint(mint)
+    }
+
+    /// Rejects any mint but the pinned one. Before the first mint nothing
+    /// is pinned and every mint passes.
+    pub fn check_mint(&self, mint: &Pubkey) -> Result<(), ProgramError> {
+        if self.mint != Pubkey::default() && self.mint != *mint {
+            return Err(LockerError::InvalidMint.into());
+        }
+        Ok(())
+    }
+
+    /// Rejects a single mint of more than `max_mint_per_tx`.
+    pub fn check_mint_amount(&self, amount: u64) -> Result<(), ProgramError> {
+        if self.max_mint_per_tx != 0 && amount > self.max_mint_per_tx {
+            return Err(LockerError::AmountAboveMaximum.into());
+        }
+        Ok(())
+    }
+
+    /// Accounts `amount` against the mint cap, resetting the running total
+    /// when `epoch` is newer than the one last recorded.
+    pub fn record_epoch_mint(&mut self, epoch: Epoch, amount: u64) -> Result<(), ProgramError> {
+        if epoch != self.current_epoch {
+            self.current_epoch = epoch;
+            self.epoch_minted = 0;
+        }
+        let epoch_minted = self.epoch_minted
+            .checked_add(amount)
+            .ok_or(LockerError::ArithmeticOverflow)?;
+        if self.epoch_mint_cap != 0 && epoch_minted > self.epoch_mint_cap {
+            return Err(LockerError::EpochCapExceeded.into());
+        }
+        self.epoch_minted = epoch_minted;
+        Ok(())
+    }
 }
 
 impl Sealed for Locker{}
@@ -33,52 +387,172 @@ impl Pack for Locker {
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
         let src = array_ref![src, 0, Locker::LEN];
         let (
+            magic,
             is_initialized,
             authority,
             total_locked,
             total_minted,
-        ) = array_refs![src, 1, 32, 8, 8];
+            epoch_mint_cap,
+            current_epoch,
+            epoch_minted,
+            total_locked_cap,
+            in_progress,
+            collateralization_ratio,
+            backup_authority,
+            large_release_threshold,
+            max_mint_per_tx,
+            pending_change_kind,
+            pending_change_value,
+            pending_change_slot,
+            eth_contract,
+            release_root,
+            local_mode,
+            metadata,
+            outbound_nonce,
+            mint_decimals,
+            underlying_decimals,
+            mint,
+        ) = array_refs![src, MAGIC_LEN, 1, 32, 8, 8, 8, 8, 8, 8, 1, 8, 32, 8, 8, 1, 32, 8, ETH_ADDRESS_LEN, 32, 1, 32, 8, 1, 1, 32];
+        if *magic != MAGIC {
+            return Err(LockerError::BadMagic.into());
+        }
         let is_initialized = match is_initialized {
             [0] => false,
             [1] => true,
             _ => return Err(ProgramError::InvalidAccountData),
         };
+        let in_progress = match in_progress {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let local_mode = match local_mode {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
         Ok(Locker{
             is_initialized,
             authority: Pubkey::new_from_array(*authority),
             total_locked: u64::from_le_bytes(*total_locked),
-            total_minted: u64::from_le_bytes(*total_minted)
+            total_minted: u64::from_le_bytes(*total_minted),
+            epoch_mint_cap: u64::from_le_bytes(*epoch_mint_cap),
+            current_epoch: u64::from_le_bytes(*current_epoch),
+            epoch_minted: u64::from_le_bytes(*epoch_minted),
+            total_locked_cap: u64::from_le_bytes(*total_locked_cap),
+            in_progress,
+            collateralization_ratio: u64::from_le_bytes(*collateralization_ratio),
+            backup_authority: Pubkey::new_from_array(*backup_authority),
+            large_release_threshold: u64::from_le_bytes(*large_release_threshold),
+            max_mint_per_tx: u64::from_le_bytes(*max_mint_per_tx),
+            pending_change: ConfigChange::unpack(pending_change_kind, pending_change_value)?,
+            pending_change_slot: u64::from_le_bytes(*pending_change_slot),
+            eth_contract: *eth_contract,
+            release_root: *release_root,
+            local_mode,
+            metadata: Pubkey::new_from_array(*metadata),
+            outbound_nonce: u64::from_le_bytes(*outbound_nonce),
+            mint_decimals: mint_decimals[0],
+            underlying_decimals: underlying_decimals[0],
+            mint: Pubkey::new_from_array(*mint),
         })
     }
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
         let dst = array_mut_ref![dst, 0, Locker::LEN];
         let (
+            magic_dst,
             is_initialized_dst,
             authority_dst,
             total_locked_dst,
             total_minted_dst,
-        ) = mut_array_refs![dst, 1, 32, 8, 8];
+            epoch_mint_cap_dst,
+            current_epoch_dst,
+            epoch_minted_dst,
+            total_locked_cap_dst,
+            in_progress_dst,
+            collateralization_ratio_dst,
+            backup_authority_dst,
+            large_release_threshold_dst,
+            max_mint_per_tx_dst,
+            pending_change_kind_dst,
+            pending_change_value_dst,
+            pending_change_slot_dst,
+            eth_contract_dst,
+            release_root_dst,
+            local_mode_dst,
+            metadata_dst,
+            outbound_nonce_dst,
+            mint_decimals_dst,
+            underlying_decimals_dst,
+            mint_dst,
+        ) = mut_array_refs![dst, MAGIC_LEN, 1, 32, 8, 8, 8, 8, 8, 8, 1, 8, 32, 8, 8, 1, 32, 8, ETH_ADDRESS_LEN, 32, 1, 32, 8, 1, 1, 32];
 
         let Locker {
             is_initialized,
             authority,
             total_locked,
             total_minted,
+            epoch_mint_cap,
+            current_epoch,
+            epoch_minted,
+            total_locked_cap,
+            in_progress,
+            collateralization_ratio,
+            backup_authority,
+            large_release_threshold,
+            max_mint_per_tx,
+            pending_change,
+            pending_change_slot,
+            eth_contract,
+            release_root,
+            local_mode,
+            metadata,
+            outbound_nonce,
+            mint_decimals,
+            underlying_decimals,
+            mint,
         } = self;
 
+        *magic_dst = MAGIC;
         is_initialized_dst[0] = *is_initialized as u8;
         authority_dst.copy_from_slice(authority.as_ref());
         *total_locked_dst = total_locked.to_le_bytes();
         *total_minted_dst = total_minted.to_le_bytes();
+        *epoch_mint_cap_dst = epoch_mint_cap.to_le_bytes();
+        *current_epoch_dst = current_epoch.to_le_bytes();
+        *epoch_minted_dst = epoch_minted.to_le_bytes();
+        *total_locked_cap_dst = total_locked_cap.to_le_bytes();
+        in_progress_dst[0] = *in_progress as u8;
+        *collateralization_ratio_dst = collateralization_ratio.to_le_bytes();
+        backup_authority_dst.copy_from_slice(backup_authority.as_ref());
+        *large_release_threshold_dst = large_release_threshold.to_le_bytes();
+        *max_mint_per_tx_dst = max_mint_per_tx.to_le_bytes();
+        ConfigChange::pack(pending_change, pending_change_kind_dst, pending_change_value_dst);
+        *pending_change_slot_dst = pending_change_slot.to_le_bytes();
+        *eth_contract_dst = *eth_contract;
+        *release_root_dst = *release_root;
+        local_mode_dst[0] = *local_mode as u8;
+        metadata_dst.copy_from_slice(metadata.as_ref());
+        *outbound_nonce_dst = outbound_nonce.to_le_bytes();
+        mint_decimals_dst[0] = *mint_decimals;
+        underlying_decimals_dst[0] = *underlying_decimals;
+        mint_dst.copy_from_slice(mint.as_ref());
     }
 }
 
+/// With the `serde` feature, serializes as
+/// `{"amount": "<decimal>", "recipient": "<hex>", "nonce": <integer>}`.
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate"))]
 pub struct BurnAndReleaseLog {
+    #[cfg_attr(feature = "serde", serde(with = "serde_u256"))]
     pub amount: U256,
+    #[cfg_attr(feature = "serde", serde(with = "serde_hex"))]
     pub recipient: [u8; DESTINATION_CHAIN_ADDRESS_LEN],
+    /// Outbound nonce of the event, unique across locks and burns
+    pub nonce: u64,
 }
 
 impl Sealed for BurnAndReleaseLog{}
@@ -87,38 +561,45 @@ impl Pack for BurnAndReleaseLog {
     const LEN: usize = LOGSIZE;
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
         let src = array_ref![src, 0, BurnAndReleaseLog::LEN];
-        let (
-            amount,
-            recipient
-        ) = array_refs![src, 32, DESTINATION_CHAIN_ADDRESS_LEN];
+        let (magic, message) = array_refs![src, MAGIC_LEN, wire::MESSAGE_LEN];
+        if *magic != MAGIC {
+            return Err(LockerError::BadMagic.into());
+        }
+        let (amount, recipient, nonce) = wire::decode_burn(message);
         Ok(BurnAndReleaseLog{
-            amount: U256::from_big_endian(&amount[..]),
-            recipient: *recipient,
+            amount,
+            recipient,
+            nonce,
         })
     }
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
         let dst = array_mut_ref![dst, 0, BurnAndReleaseLog::LEN];
-        let (
-            amount_dst,
-            recipient_dst
-        ) = mut_array_refs![dst, 32, DESTINATION_CHAIN_ADDRESS_LEN];
+        let (magic_dst, message_dst) = mut_array_refs![dst, MAGIC_LEN, wire::MESSAGE_LEN];
 
         let BurnAndReleaseLog {
             amount,
-            recipient
+            recipient,
+            nonce
         } = self;
 
-        amount.to_big_endian(&mut amount_dst[..]);
-        recipient_dst.copy_from_slice(&recipient[..]);
+        *magic_dst = MAGIC;
+        wire::encode_burn(amount, recipient, *nonce, message_dst);
     }
 }
 
+/// With the `serde` feature, serializes as
+/// `{"amount": "<decimal>", "recipient": "<hex>", "nonce": <integer>}`.
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate"))]
 pub struct LockAndMintLog {
+    #[cfg_attr(feature = "serde", serde(with = "serde_u256"))]
     pub amount: U256,
+    #[cfg_attr(feature = "serde", serde(with = "serde_hex"))]
     pub recipient: [u8; DESTINATION_CHAIN_ADDRESS_LEN],
+    /// Outbound nonce of the event, unique across locks and burns
+    pub nonce: u64,
 }
 
 impl Sealed for LockAndMintLog{}
@@ -127,30 +608,650 @@ impl Pack for LockAndMintLog {
     const LEN: usize = LOGSIZE;
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
         let src = array_ref![src, 0, LockAndMintLog::LEN];
+        let (magic, message) = array_refs![src, MAGIC_LEN, wire::MESSAGE_LEN];
+        if *magic != MAGIC {
+            return Err(LockerError::BadMagic.into());
+        }
+        let (amount, recipient, nonce) = wire::decode_lock(message);
+        Ok(LockAndMintLog{
+            amount,
+            recipient,
+            nonce,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, LockAndMintLog::LEN];
+        let (magic_dst, message_dst) = mut_array_refs![dst, MAGIC_LEN, wire::MESSAGE_LEN];
+
+        let LockAndMintLog {
+            amount,
+            recipient,
+            nonce
+        } = self;
+
+        *magic_dst = MAGIC;
+        wire::encode_lock(amount, recipient, *nonce, message_dst);
+    }
+}
+
+/// Returned after the `InstructionResult` of every `LockAndMint`, so a
+/// client gets the outcome of the lock without fetching the state and log
+/// afterwards.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LockReceipt {
+    pub amount: u64,
+    pub underlying_amount: U256,
+    pub recipient: [u8; DESTINATION_CHAIN_ADDRESS_LEN],
+    pub total_locked: u64,
+    pub vault_balance: u64,
+    /// Outbound nonce the lock was logged with, 0 for a local lock, which
+    /// is never logged
+    pub nonce: u64,
+}
+
+impl Sealed for LockReceipt{}
+
+impl Pack for LockReceipt {
+    const LEN: usize = RECEIPTSIZE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, LockReceipt::LEN];
         let (
             amount,
-            recipient
-        ) = array_refs![src, 32, DESTINATION_CHAIN_ADDRESS_LEN];
-        Ok(LockAndMintLog{
-            amount: U256::from_big_endian(&amount[..]),
+            underlying_amount,
+            recipient,
+            total_locked,
+            vault_balance,
+            nonce
+        ) = array_refs![src, 8, 32, DESTINATION_CHAIN_ADDRESS_LEN, 8, 8, 8];
+        Ok(LockReceipt{
+            amount: u64::from_le_bytes(*amount),
+            underlying_amount: U256::from_big_endian(&underlying_amount[..]),
             recipient: *recipient,
+            total_locked: u64::from_le_bytes(*total_locked),
+            vault_balance: u64::from_le_bytes(*vault_balance),
+            nonce: u64::from_le_bytes(*nonce),
         })
     }
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let dst = array_mut_ref![dst, 0, LockAndMintLog::LEN];
+        let dst = array_mut_ref![dst, 0, LockReceipt::LEN];
         let (
             amount_dst,
-            recipient_dst
-        ) = mut_array_refs![dst, 32, DESTINATION_CHAIN_ADDRESS_LEN];
+            underlying_amount_dst,
+            recipient_dst,
+            total_locked_dst,
+            vault_balance_dst,
+            nonce_dst
+        ) = mut_array_refs![dst, 8, 32, DESTINATION_CHAIN_ADDRESS_LEN, 8, 8, 8];
 
-        let LockAndMintLog {
+        let LockReceipt {
             amount,
-            recipient
+            underlying_amount,
+            recipient,
+            total_locked,
+            vault_balance,
+            nonce
         } = self;
 
-        amount.to_big_endian(&mut amount_dst[..]);
+        *amount_dst = amount.to_le_bytes();
+        underlying_amount.to_big_endian(&mut underlying_amount_dst[..]);
         recipient_dst.copy_from_slice(&recipient[..]);
+        *total_locked_dst = total_locked.to_le_bytes();
+        *vault_balance_dst = vault_balance.to_le_bytes();
+        *nonce_dst = nonce.to_le_bytes();
+    }
+}
+
+/// Emitted with `sol_log_data` for every `Mint`, tying it to the Ethereum
+/// event it was minted for.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MintReceipt {
+    pub amount: u64,
+    pub sequence: u64,
+    pub recipient: Pubkey,
+    pub eth_tx_hash: [u8; 32],
+    pub eth_log_index: u64,
+    pub eth_block_number: u64,
+}
+
+impl Sealed for MintReceipt{}
+
+impl Pack for MintReceipt {
+    const LEN: usize = MINT_RECEIPT_SIZE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, MintReceipt::LEN];
+        let (
+            amount,
+            sequence,
+            recipient,
+            eth_tx_hash,
+            eth_log_index,
+            eth_block_number
+        ) = array_refs![src, 8, 8, 32, 32, 8, 8];
+        Ok(MintReceipt{
+            amount: u64::from_le_bytes(*amount),
+            sequence: u64::from_le_bytes(*sequence),
+            recipient: Pubkey::new_from_array(*recipient),
+            eth_tx_hash: *eth_tx_hash,
+            eth_log_index: u64::from_le_bytes(*eth_log_index),
+            eth_block_number: u64::from_le_bytes(*eth_block_number),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, MintReceipt::LEN];
+        let (
+            amount_dst,
+            sequence_dst,
+            recipient_dst,
+            eth_tx_hash_dst,
+            eth_log_index_dst,
+            eth_block_number_dst
+        ) = mut_array_refs![dst, 8, 8, 32, 32, 8, 8];
+
+        let MintReceipt {
+            amount,
+            sequence,
+            recipient,
+            eth_tx_hash,
+            eth_log_index,
+            eth_block_number
+        } = self;
+
+        *amount_dst = amount.to_le_bytes();
+        *sequence_dst = sequence.to_le_bytes();
+        recipient_dst.copy_from_slice(recipient.as_ref());
+        *eth_tx_hash_dst = *eth_tx_hash;
+        *eth_log_index_dst = eth_log_index.to_le_bytes();
+        *eth_block_number_dst = eth_block_number.to_le_bytes();
+    }
+}
+
+/// Returned by `GetHealth` through return data, so monitoring gets the key
+/// metrics in one call.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HealthSummary {
+    pub total_locked: u64,
+    pub total_minted: u64,
+    pub collateralization_ratio: u64,
+    /// total_locked minus total_minted, in underlying units
+    pub in_flight: U256,
+    pub vault_balance: u64,
+    pub in_progress: bool,
+}
+
+impl Sealed for HealthSummary{}
+
+impl Pack for HealthSummary {
+    const LEN: usize = HEALTH_SUMMARY_SIZE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, HealthSummary::LEN];
+        let (
+            total_locked,
+            total_minted,
+            collateralization_ratio,
+            in_flight,
+            vault_balance,
+            in_progress
+        ) = array_refs![src, 8, 8, 8, 32, 8, 1];
+        let in_progress = match in_progress {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(HealthSummary{
+            total_locked: u64::from_le_bytes(*total_locked),
+            total_minted: u64::from_le_bytes(*total_minted),
+            collateralization_ratio: u64::from_le_bytes(*collateralization_ratio),
+            in_flight: U256::from_big_endian(&in_flight[..]),
+            vault_balance: u64::from_le_bytes(*vault_balance),
+            in_progress,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, HealthSummary::LEN];
+        let (
+            total_locked_dst,
+            total_minted_dst,
+            collateralization_ratio_dst,
+            in_flight_dst,
+            vault_balance_dst,
+            in_progress_dst
+        ) = mut_array_refs![dst, 8, 8, 8, 32, 8, 1];
+
+        let HealthSummary {
+            total_locked,
+            total_minted,
+            collateralization_ratio,
+            in_flight,
+            vault_balance,
+            in_progress
+        } = self;
+
+        *total_locked_dst = total_locked.to_le_bytes();
+        *total_minted_dst = total_minted.to_le_bytes();
+        *collateralization_ratio_dst = collateralization_ratio.to_le_bytes();
+        in_flight.to_big_endian(&mut in_flight_dst[..]);
+        *vault_balance_dst = vault_balance.to_le_bytes();
+        in_progress_dst[0] = *in_progress as u8;
+    }
+}
+
+/// Cumulative amount released to a single destination, stored at the PDA
+/// derived from `[b"Locker", b"Released", destination]`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ReleaseRecord {
+    pub is_initialized: bool,
+    pub destination: Pubkey,
+    pub total_released: u64,
+    /// Slot of the most recent release to `destination`
+    pub last_release_slot: Slot,
+}
+
+impl ReleaseRecord {
+    /// Adds a release of `amount` made at `slot`.
+    pub fn record_release(&mut self, amount: u64, slot: Slot) -> Result<(), ProgramError> {
+        self.total_released = self.total_released
+            .checked_add(amount)
+            .ok_or(LockerError::ArithmeticOverflow)?;
+        self.last_release_slot = slot;
+        Ok(())
+    }
+}
+
+impl Sealed for ReleaseRecord{}
+
+impl IsInitialized for ReleaseRecord{
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for ReleaseRecord {
+    const LEN: usize = RELEASE_RECORD_SIZE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, ReleaseRecord::LEN];
+        let (
+            is_initialized,
+            destination,
+            total_released,
+            last_release_slot,
+        ) = array_refs![src, 1, 32, 8, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(ReleaseRecord{
+            is_initialized,
+            destination: Pubkey::new_from_array(*destination),
+            total_released: u64::from_le_bytes(*total_released),
+            last_release_slot: u64::from_le_bytes(*last_release_slot),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, ReleaseRecord::LEN];
+        let (
+            is_initialized_dst,
+            destination_dst,
+            total_released_dst,
+            last_release_slot_dst,
+        ) = mut_array_refs![dst, 1, 32, 8, 8];
+
+        let ReleaseRecord {
+            is_initialized,
+            destination,
+            total_released,
+            last_release_slot,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        destination_dst.copy_from_slice(destination.as_ref());
+        *total_released_dst = total_released.to_le_bytes();
+        *last_release_slot_dst = last_release_slot.to_le_bytes();
+    }
+}
+
+/// Mint recorded by the authority for `recipient` to pull with `ClaimMint`,
+/// stored at the PDA derived from
+/// `[b"Locker", b"Claim", eth_tx_hash, eth_log_index]`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Claim {
+    pub is_initialized: bool,
+    pub claimed: bool,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub sequence: u64,
+    pub eth_tx_hash: [u8; 32],
+    pub eth_log_index: u64,
+}
+
+impl Sealed for Claim{}
+
+impl IsInitialized for Claim{
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Claim {
+    const LEN: usize = CLAIM_SIZE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Claim::LEN];
+        let (
+            is_initialized,
+            claimed,
+            recipient,
+            amount,
+            sequence,
+            eth_tx_hash,
+            eth_log_index,
+        ) = array_refs![src, 1, 1, 32, 8, 8, 32, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let claimed = match claimed {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(Claim{
+            is_initialized,
+            claimed,
+            recipient: Pubkey::new_from_array(*recipient),
+            amount: u64::from_le_bytes(*amount),
+            sequence: u64::from_le_bytes(*sequence),
+            eth_tx_hash: *eth_tx_hash,
+            eth_log_index: u64::from_le_bytes(*eth_log_index),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Claim::LEN];
+        let (
+            is_initialized_dst,
+            claimed_dst,
+            recipient_dst,
+            amount_dst,
+            sequence_dst,
+            eth_tx_hash_dst,
+            eth_log_index_dst,
+        ) = mut_array_refs![dst, 1, 1, 32, 8, 8, 32, 8];
+
+        let Claim {
+            is_initialized,
+            claimed,
+            recipient,
+            amount,
+            sequence,
+            eth_tx_hash,
+            eth_log_index,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        claimed_dst[0] = *claimed as u8;
+        recipient_dst.copy_from_slice(recipient.as_ref());
+        *amount_dst = amount.to_le_bytes();
+        *sequence_dst = sequence.to_le_bytes();
+        *eth_tx_hash_dst = *eth_tx_hash;
+        *eth_log_index_dst = eth_log_index.to_le_bytes();
+    }
+}
+
+/// Scales `total_locked` from `locked_decimals` and `total_minted` from
+/// `minted_decimals` up to the finer of the two, so the totals can be
+/// compared. Shared by the solvency check and the collateralization ratio.
+pub fn scale_totals(
+    total_locked: u64,
+    locked_decimals: u8,
+    total_minted: u64,
+    minted_decimals: u8,
+) -> Result<(U256, U256), ProgramError> {
+    let decimals = locked_decimals.max(minted_decimals);
+    let scale = |amount: u64, from: u8| -> Result<U256, ProgramError> {
+        U256::from(10u64)
+            .checked_pow(U256::from(decimals - from))
+            .and_then(|factor| factor.checked_mul(U256::from(amount)))
+            .ok_or_else(|| LockerError::UnexpectedDecimalConversion.into())
+    };
+    Ok((scale(total_locked, locked_decimals)?, scale(total_minted, minted_decimals)?))
+}
+
+/// Sequences tracked by one processed-event bitmap account. Each window
+/// of sequences gets its own fixed-size PDA, so no account ever has to grow.
+pub const BITS_PER_ACCOUNT: u64 = 8192;
+pub const BITMAP_SIZE: usize = (BITS_PER_ACCOUNT / 8) as usize;
+
+/// Index of the bitmap account holding `sequence`.
+pub fn bitmap_window(sequence: u64) -> u64 {
+    sequence / BITS_PER_ACCOUNT
+}
+
+/// Whether bit `index` of the bitmap is set, bits past the end read as unset.
+pub fn is_set(bitmap: &[u8], index: u64) -> bool {
+    bitmap
+        .get((index / 8) as usize)
+        .map_or(false, |byte| byte & (1 << (index % 8)) != 0)
+}
+
+pub fn set_bit(bitmap: &mut [u8], index: u64) -> Result<(), ProgramError> {
+    let byte = bitmap
+        .get_mut((index / 8) as usize)
+        .ok_or(ProgramError::AccountDataTooSmall)?;
+    *byte |= 1 << (index % 8);
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+mod serde_u256 {
+    use serde_crate::{de::Error, Deserialize, Deserializer, Serializer};
+    use spl_math::uint::U256;
+
+    pub fn serialize<S: Serializer>(amount: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&amount.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<U256, D::Error> {
+        let amount = String::deserialize(deserializer)?;
+        U256::from_dec_str(&amount).map_err(|_| D::Error::custom("invalid decimal amount"))
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_hex {
+    use serde_crate::{de::Error, Deserialize, Deserializer, Serializer};
+    use crate::types::DESTINATION_CHAIN_ADDRESS_LEN;
+
+    pub fn serialize<S: Serializer>(
+        recipient: &[u8; DESTINATION_CHAIN_ADDRESS_LEN],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let hex: String = recipient.iter().map(|byte| format!("{:02x}", byte)).collect();
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<[u8; DESTINATION_CHAIN_ADDRESS_LEN], D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() != 2 * DESTINATION_CHAIN_ADDRESS_LEN {
+            return Err(D::Error::custom("invalid recipient length"));
+        }
+        let mut recipient = [0u8; DESTINATION_CHAIN_ADDRESS_LEN];
+        for (i, byte) in recipient.iter_mut().enumerate() {
+            let pair = hex.get(2 * i..2 * i + 2).ok_or_else(|| D::Error::custom("invalid recipient hex"))?;
+            *byte = u8::from_str_radix(pair, 16).map_err(D::Error::custom)?;
+        }
+        Ok(recipient)
+    }
+}
+
+/// Returned by `GetRoles` through return data, so frontends can show who
+/// controls the bridge without decoding the state layout.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RolesView {
+    pub authority: Pubkey,
+    pub backup_authority: Pubkey,
+}
+
+impl Sealed for RolesView{}
+
+impl Pack for RolesView {
+    const LEN: usize = ROLES_VIEW_SIZE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, RolesView::LEN];
+        let (authority, backup_authority) = array_refs![src, 32, 32];
+        Ok(RolesView{
+            authority: Pubkey::new_from_array(*authority),
+            backup_authority: Pubkey::new_from_array(*backup_authority),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, RolesView::LEN];
+        let (authority_dst, backup_authority_dst) = mut_array_refs![dst, 32, 32];
+
+        let RolesView {
+            authority,
+            backup_authority,
+        } = self;
+
+        authority_dst.copy_from_slice(authority.as_ref());
+        backup_authority_dst.copy_from_slice(backup_authority.as_ref());
+    }
+}
+
+/// Returned through return data by every instruction that changes the
+/// locker state, so a simulated transaction yields a structured outcome.
+/// An instruction with more to report appends its own payload after it.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InstructionResult {
+    pub success: bool,
+    /// Amount the instruction moved, 0 when it moved nothing
+    pub amount: u64,
+    pub total_locked: u64,
+    pub total_minted: u64,
+}
+
+impl Sealed for InstructionResult{}
+
+impl Pack for InstructionResult {
+    const LEN: usize = INSTRUCTION_RESULT_SIZE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, InstructionResult::LEN];
+        let (success, amount, total_locked, total_minted) = array_refs![src, 1, 8, 8, 8];
+        let success = match success {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(InstructionResult{
+            success,
+            amount: u64::from_le_bytes(*amount),
+            total_locked: u64::from_le_bytes(*total_locked),
+            total_minted: u64::from_le_bytes(*total_minted),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, InstructionResult::LEN];
+        let (success_dst, amount_dst, total_locked_dst, total_minted_dst) = mut_array_refs![dst, 1, 8, 8, 8];
+
+        let InstructionResult {
+            success,
+            amount,
+            total_locked,
+            total_minted,
+        } = self;
+
+        success_dst[0] = *success as u8;
+        *amount_dst = amount.to_le_bytes();
+        *total_locked_dst = total_locked.to_le_bytes();
+        *total_minted_dst = total_minted.to_le_bytes();
+    }
+}
+
+/// A release recorded by `StartRelease` and paid down by `FillRelease`
+/// until `filled` reaches `total`. Lives at the PDA seeded with
+/// `[b"Locker", b"Release", sequence]`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PendingRelease {
+    pub is_initialized: bool,
+    pub destination: Pubkey,
+    pub total: u64,
+    pub filled: u64,
+    pub sequence: u64,
+}
+
+impl Sealed for PendingRelease{}
+
+impl IsInitialized for PendingRelease{
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for PendingRelease {
+    const LEN: usize = PENDING_RELEASE_SIZE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, PendingRelease::LEN];
+        let (
+            is_initialized,
+            destination,
+            total,
+            filled,
+            sequence,
+        ) = array_refs![src, 1, 32, 8, 8, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(PendingRelease{
+            is_initialized,
+            destination: Pubkey::new_from_array(*destination),
+            total: u64::from_le_bytes(*total),
+            filled: u64::from_le_bytes(*filled),
+            sequence: u64::from_le_bytes(*sequence),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, PendingRelease::LEN];
+        let (
+            is_initialized_dst,
+            destination_dst,
+            total_dst,
+            filled_dst,
+            sequence_dst,
+        ) = mut_array_refs![dst, 1, 32, 8, 8, 8];
+
+        let PendingRelease {
+            is_initialized,
+            destination,
+            total,
+            filled,
+            sequence,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        destination_dst.copy_from_slice(destination.as_ref());
+        *total_dst = total.to_le_bytes();
+        *filled_dst = filled.to_le_bytes();
+        *sequence_dst = sequence.to_le_bytes();
     }
 }
 
@@ -159,6 +1260,33 @@ mod tests {
     use super::*;
     use rand::RngCore;
 
+    // byte offsets of the `Locker` fields, in the order `array_refs!` lays
+    // them out
+    const IS_INITIALIZED_OFFSET: usize = MAGIC_LEN;
+    const AUTHORITY_OFFSET: usize = IS_INITIALIZED_OFFSET + 1;
+    const TOTAL_LOCKED_OFFSET: usize = AUTHORITY_OFFSET + 32;
+    const TOTAL_MINTED_OFFSET: usize = TOTAL_LOCKED_OFFSET + 8;
+    const EPOCH_MINT_CAP_OFFSET: usize = TOTAL_MINTED_OFFSET + 8;
+    const CURRENT_EPOCH_OFFSET: usize = EPOCH_MINT_CAP_OFFSET + 8;
+    const EPOCH_MINTED_OFFSET: usize = CURRENT_EPOCH_OFFSET + 8;
+    const TOTAL_LOCKED_CAP_OFFSET: usize = EPOCH_MINTED_OFFSET + 8;
+    const IN_PROGRESS_OFFSET: usize = TOTAL_LOCKED_CAP_OFFSET + 8;
+    const COLLATERALIZATION_RATIO_OFFSET: usize = IN_PROGRESS_OFFSET + 1;
+    const BACKUP_AUTHORITY_OFFSET: usize = COLLATERALIZATION_RATIO_OFFSET + 8;
+    const LARGE_RELEASE_THRESHOLD_OFFSET: usize = BACKUP_AUTHORITY_OFFSET + 32;
+    const MAX_MINT_PER_TX_OFFSET: usize = LARGE_RELEASE_THRESHOLD_OFFSET + 8;
+    const PENDING_CHANGE_KIND_OFFSET: usize = MAX_MINT_PER_TX_OFFSET + 8;
+    const PENDING_CHANGE_VALUE_OFFSET: usize = PENDING_CHANGE_KIND_OFFSET + 1;
+    const PENDING_CHANGE_SLOT_OFFSET: usize = PENDING_CHANGE_VALUE_OFFSET + 32;
+    const ETH_CONTRACT_OFFSET: usize = PENDING_CHANGE_SLOT_OFFSET + 8;
+    const RELEASE_ROOT_OFFSET: usize = ETH_CONTRACT_OFFSET + ETH_ADDRESS_LEN;
+    const LOCAL_MODE_OFFSET: usize = RELEASE_ROOT_OFFSET + 32;
+    const METADATA_OFFSET: usize = LOCAL_MODE_OFFSET + 1;
+    const OUTBOUND_NONCE_OFFSET: usize = METADATA_OFFSET + 32;
+    const MINT_DECIMALS_OFFSET: usize = OUTBOUND_NONCE_OFFSET + 8;
+    const UNDERLYING_DECIMALS_OFFSET: usize = MINT_DECIMALS_OFFSET + 1;
+    const MINT_OFFSET: usize = UNDERLYING_DECIMALS_OFFSET + 1;
+
     fn rand_bytes(n: usize) -> Vec<u8> {
         let mut output = vec![0u8; n];
         rand::thread_rng().fill_bytes(output.as_mut_slice());
@@ -170,15 +1298,303 @@ mod tests {
         let amount = rand_bytes(32);
         let mut amount_arr = [0u8; 32];
         amount_arr.copy_from_slice(amount.as_slice());
-        let recipient = rand_bytes(25);
+        let recipient = rand_bytes(DESTINATION_CHAIN_ADDRESS_LEN);
         let mut recipient_arr = [0u8; DESTINATION_CHAIN_ADDRESS_LEN];
-        recipient_arr[0..25].copy_from_slice(recipient.as_slice());
+        recipient_arr.copy_from_slice(recipient.as_slice());
         let burn_log = BurnAndReleaseLog {
             amount: U256::from_big_endian(amount.as_slice()),
             recipient: recipient_arr,
+            nonce: u64::MAX,
         };
-        let mut burn_log_bytes = [0u8; 64];
+        let mut burn_log_bytes = [0u8; LOGSIZE];
         let res = BurnAndReleaseLog::pack(burn_log, &mut burn_log_bytes);
         assert!(res.is_ok());
+        assert_eq!(BurnAndReleaseLog::unpack_from_slice(&burn_log_bytes).unwrap(), burn_log);
+    }
+
+    #[test]
+    fn test_epoch_mint_cap_across_epochs() {
+        let mut locker = Locker {
+            is_initialized: true,
+            epoch_mint_cap: 100,
+            current_epoch: 5,
+            ..Locker::default()
+        };
+        assert!(locker.record_epoch_mint(5, 60).is_ok());
+        assert_eq!(
+            locker.record_epoch_mint(5, 50),
+            Err(LockerError::EpochCapExceeded.into())
+        );
+        assert_eq!(locker.epoch_minted, 60);
+
+        assert!(locker.record_epoch_mint(6, 50).is_ok());
+        assert_eq!(locker.current_epoch, 6);
+        assert_eq!(locker.epoch_minted, 50);
+        assert!(locker.record_epoch_mint(6, 50).is_ok());
+        assert_eq!(
+            locker.record_epoch_mint(6, 1),
+            Err(LockerError::EpochCapExceeded.into())
+        );
+    }
+
+    #[test]
+    fn test_epoch_mint_cap_disabled() {
+        let mut locker = Locker::default();
+        assert!(locker.record_epoch_mint(1, u64::MAX).is_ok());
+        assert_eq!(
+            locker.record_epoch_mint(1, 1),
+            Err(LockerError::ArithmeticOverflow.into())
+        );
     }
-}
\ No newline at end of file
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_log_json_round_trip() {
+        let log = LockAndMintLog {
+            amount: U256::from(1_000_000_000u64) * U256::exp10(9),
+            recipient: [0xab; DESTINATION_CHAIN_ADDRESS_LEN],
+            nonce: 42,
+        };
+        let json = serde_json::to_string(&log).unwrap();
+        assert_eq!(
+            json,
+            format!(
+                "{{\"amount\":\"1000000000000000000\",\"recipient\":\"{}\",\"nonce\":42}}",
+                "ab".repeat(DESTINATION_CHAIN_ADDRESS_LEN)
+            )
+        );
+        let decoded: LockAndMintLog = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, log);
+    }
+
+    #[test]
+    fn test_collateralization_ratio() {
+        let mut locker = Locker {
+            total_locked: 1_500,
+            mint_decimals: 9,
+            ..Locker::default()
+        };
+        locker.update_collateralization_ratio().unwrap();
+        assert_eq!(locker.collateralization_ratio, u64::MAX);
+
+        locker.total_minted = 1_000;
+        locker.update_collateralization_ratio().unwrap();
+        assert_eq!(locker.collateralization_ratio, 15_000);
+
+        locker.total_locked = u64::MAX;
+        locker.total_minted = 1;
+        locker.update_collateralization_ratio().unwrap();
+        assert_eq!(locker.collateralization_ratio, u64::MAX);
+
+        // 1 SOL locked backing 1 token of a 6 decimal mint
+        locker.mint_decimals = 6;
+        locker.total_locked = 1_000_000_000;
+        locker.total_minted = 1_000_000;
+        locker.update_collateralization_ratio().unwrap();
+        assert_eq!(locker.collateralization_ratio, 10_000);
+
+        // and of an 18 decimal mint
+        locker.mint_decimals = 18;
+        locker.total_minted = 500_000_000_000_000_000;
+        locker.update_collateralization_ratio().unwrap();
+        assert_eq!(locker.collateralization_ratio, 20_000);
+    }
+
+    #[test]
+    fn test_bitmap_across_byte_boundaries() {
+        let mut bitmap = vec![0u8; 3];
+        for index in [7u64, 8, 15, 16].iter() {
+            assert!(!is_set(&bitmap, *index));
+            assert!(set_bit(&mut bitmap, *index).is_ok());
+            assert!(is_set(&bitmap, *index));
+        }
+        assert_eq!(bitmap, vec![0b1000_0000, 0b1000_0001, 0b0000_0001]);
+        assert!(!is_set(&bitmap, 9));
+        assert!(!is_set(&bitmap, 24));
+        assert_eq!(set_bit(&mut bitmap, 24), Err(ProgramError::AccountDataTooSmall));
+    }
+
+    #[test]
+    fn test_bitmap_windows() {
+        assert_eq!(bitmap_window(0), 0);
+        assert_eq!(bitmap_window(BITS_PER_ACCOUNT - 1), 0);
+        assert_eq!(bitmap_window(BITS_PER_ACCOUNT), 1);
+        assert_eq!(bitmap_window(u64::MAX), u64::MAX / BITS_PER_ACCOUNT);
+        // the last bit of a window still fits in its account
+        let mut bitmap = vec![0u8; BITMAP_SIZE];
+        assert!(set_bit(&mut bitmap, u64::MAX % BITS_PER_ACCOUNT).is_ok());
+    }
+
+    #[test]
+    fn test_missing_magic_rejected() {
+        let mut state = [0u8; STATESIZE];
+        Locker::pack(Locker { is_initialized: true, ..Locker::default() }, &mut state).unwrap();
+        assert_eq!(&state[..MAGIC_LEN], &MAGIC);
+        assert!(Locker::unpack(&state).is_ok());
+        state[..MAGIC_LEN].copy_from_slice(&[0u8; MAGIC_LEN]);
+        assert!(matches!(
+            Locker::unpack(&state),
+            Err(ProgramError::Custom(code)) if code == LockerError::BadMagic as u32
+        ));
+
+        let log = [0u8; LOGSIZE];
+        assert!(matches!(
+            LockAndMintLog::unpack_from_slice(&log),
+            Err(ProgramError::Custom(code)) if code == LockerError::BadMagic as u32
+        ));
+        assert!(matches!(
+            BurnAndReleaseLog::unpack_from_slice(&log),
+            Err(ProgramError::Custom(code)) if code == LockerError::BadMagic as u32
+        ));
+    }
+
+    #[test]
+    fn test_log_amount_max_round_trip() {
+        let recipient = [0xabu8; DESTINATION_CHAIN_ADDRESS_LEN];
+        let mut data = [0u8; LOGSIZE];
+
+        let mint_log = LockAndMintLog { amount: U256::MAX, recipient, nonce: u64::MAX };
+        LockAndMintLog::pack(mint_log, &mut data).unwrap();
+        assert_eq!(&data[MAGIC_LEN..MAGIC_LEN + LOG_AMOUNT_LEN], &[0xffu8; LOG_AMOUNT_LEN][..]);
+        assert_eq!(LockAndMintLog::unpack_from_slice(&data).unwrap(), mint_log);
+
+        let burn_log = BurnAndReleaseLog { amount: U256::MAX, recipient, nonce: u64::MAX };
+        BurnAndReleaseLog::pack(burn_log, &mut data).unwrap();
+        assert_eq!(BurnAndReleaseLog::unpack_from_slice(&data).unwrap(), burn_log);
+    }
+
+    #[test]
+    fn test_mint_receipt_round_trip() {
+        let receipt = MintReceipt {
+            amount: 100,
+            sequence: 7,
+            recipient: Pubkey::new_unique(),
+            eth_tx_hash: [0x11u8; 32],
+            eth_log_index: 3,
+            eth_block_number: 15_000_000,
+        };
+        let mut data = [0u8; MINT_RECEIPT_SIZE];
+        MintReceipt::pack(receipt, &mut data).unwrap();
+        assert_eq!(MintReceipt::unpack_from_slice(&data).unwrap(), receipt);
+    }
+
+    #[test]
+    fn test_pending_change_round_trip() {
+        let backup_authority = Pubkey::new_unique();
+        for pending_change in [
+            None,
+            Some(ConfigChange::EpochMintCap(1)),
+            Some(ConfigChange::DepositCap(u64::MAX)),
+            Some(ConfigChange::BackupAuthority(backup_authority)),
+            Some(ConfigChange::LargeReleaseThreshold(3)),
+            Some(ConfigChange::MaxMintPerTx(4)),
+            Some(ConfigChange::EthContract([5u8; ETH_ADDRESS_LEN])),
+            Some(ConfigChange::LocalMode(true)),
+            Some(ConfigChange::Limits(ConfigLimits {
+                epoch_mint_cap: 1,
+                deposit_cap: 2,
+                large_release_threshold: 3,
+                max_mint_per_tx: u64::MAX,
+            })),
+        ] {
+            let locker = Locker {
+                is_initialized: true,
+                authority: Pubkey::new_unique(),
+                pending_change,
+                pending_change_slot: 216_000,
+                ..Locker::default()
+            };
+            let mut data = [0u8; STATESIZE];
+            Locker::pack(locker, &mut data).unwrap();
+            assert_eq!(Locker::unpack(&data).unwrap(), locker);
+        }
+
+        let mut data = [0u8; STATESIZE];
+        Locker::pack(Locker { is_initialized: true, ..Locker::default() }, &mut data).unwrap();
+        data[PENDING_CHANGE_KIND_OFFSET] = 10;
+        assert_eq!(Locker::unpack(&data), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn test_locker_field_offsets() {
+        let authority = Pubkey::new_unique();
+        let backup_authority = Pubkey::new_unique();
+        let metadata = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let locker = Locker {
+            is_initialized: true,
+            authority,
+            total_locked: 1,
+            total_minted: 2,
+            epoch_mint_cap: 3,
+            current_epoch: 4,
+            epoch_minted: 5,
+            total_locked_cap: 6,
+            in_progress: true,
+            collateralization_ratio: 7,
+            backup_authority,
+            large_release_threshold: 8,
+            max_mint_per_tx: 9,
+            pending_change: Some(ConfigChange::MaxMintPerTx(10)),
+            pending_change_slot: 11,
+            eth_contract: [12u8; ETH_ADDRESS_LEN],
+            release_root: [13u8; 32],
+            local_mode: true,
+            metadata,
+            outbound_nonce: 14,
+            mint_decimals: 15,
+            underlying_decimals: 16,
+            mint,
+        };
+        let mut data = [0u8; STATESIZE];
+        Locker::pack(locker, &mut data).unwrap();
+        let u64_at = |offset: usize| u64::from_le_bytes(*array_ref![data, offset, 8]);
+        let key_at = |offset: usize| Pubkey::new_from_array(*array_ref![data, offset, 32]);
+
+        assert_eq!(&data[..MAGIC_LEN], &MAGIC);
+        assert_eq!(data[IS_INITIALIZED_OFFSET], 1);
+        assert_eq!(key_at(AUTHORITY_OFFSET), authority);
+        assert_eq!(u64_at(TOTAL_LOCKED_OFFSET), 1);
+        assert_eq!(u64_at(TOTAL_MINTED_OFFSET), 2);
+        assert_eq!(u64_at(EPOCH_MINT_CAP_OFFSET), 3);
+        assert_eq!(u64_at(CURRENT_EPOCH_OFFSET), 4);
+        assert_eq!(u64_at(EPOCH_MINTED_OFFSET), 5);
+        assert_eq!(u64_at(TOTAL_LOCKED_CAP_OFFSET), 6);
+        assert_eq!(data[IN_PROGRESS_OFFSET], 1);
+        assert_eq!(u64_at(COLLATERALIZATION_RATIO_OFFSET), 7);
+        assert_eq!(key_at(BACKUP_AUTHORITY_OFFSET), backup_authority);
+        assert_eq!(u64_at(LARGE_RELEASE_THRESHOLD_OFFSET), 8);
+        assert_eq!(u64_at(MAX_MINT_PER_TX_OFFSET), 9);
+        assert_eq!(data[PENDING_CHANGE_KIND_OFFSET], 5);
+        assert_eq!(u64_at(PENDING_CHANGE_VALUE_OFFSET), 10);
+        assert_eq!(u64_at(PENDING_CHANGE_SLOT_OFFSET), 11);
+        assert_eq!(&data[ETH_CONTRACT_OFFSET..RELEASE_ROOT_OFFSET], &[12u8; ETH_ADDRESS_LEN][..]);
+        assert_eq!(&data[RELEASE_ROOT_OFFSET..LOCAL_MODE_OFFSET], &[13u8; 32][..]);
+        assert_eq!(data[LOCAL_MODE_OFFSET], 1);
+        assert_eq!(key_at(METADATA_OFFSET), metadata);
+        assert_eq!(u64_at(OUTBOUND_NONCE_OFFSET), 14);
+        assert_eq!(data[MINT_DECIMALS_OFFSET], 15);
+        assert_eq!(data[UNDERLYING_DECIMALS_OFFSET], 16);
+        assert_eq!(key_at(MINT_OFFSET), mint);
+        assert_eq!(MINT_OFFSET + 32, STATESIZE);
+    }
+
+    #[test]
+    fn test_unpack_legacy_state() {
+        let authority = Pubkey::new_unique();
+        let mut data = vec![1u8];
+        data.extend_from_slice(authority.as_ref());
+        data.extend_from_slice(&1_000u64.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+        assert_eq!(Locker::unpack_legacy(&data).unwrap(), Locker {
+            is_initialized: true,
+            authority,
+            total_locked: 1_000,
+            collateralization_ratio: u64::MAX,
+            ..Locker::default()
+        });
+
+        data.push(0);
+        assert_eq!(Locker::unpack_legacy(&data), Err(ProgramError::InvalidAccountData));
+    }
+}
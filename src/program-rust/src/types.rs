@@ -1 +1,10 @@
-pub const DESTINATION_CHAIN_ADDRESS_LEN: usize = 20usize;
\ No newline at end of file
+pub const DESTINATION_CHAIN_ADDRESS_LEN: usize = 20usize;
+pub const ETH_ADDRESS_LEN: usize = 20usize;
+pub const MAX_DECIMALS: u8 = 38u8;
+/// Decimals of the token on Ethereum when `Initialize` leaves them out
+pub const DEFAULT_UNDERLYING_DECIMALS: u8 = 18u8;
+
+/// Metaplex token metadata program, owner of every metadata account
+pub mod mpl_token_metadata {
+    solana_program::declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+}
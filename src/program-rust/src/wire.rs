@@ -0,0 +1,91 @@
+//! Byte layouts of the messages read by the Ethereum side. Log accounts
+//! hold one message behind their magic header and relayers forward it as
+//! is, so this is the only place the cross-chain format is defined.
+
+use spl_math::uint::U256;
+use arrayref::{array_refs, mut_array_refs};
+use crate::types::DESTINATION_CHAIN_ADDRESS_LEN;
+
+/// Leads every `sol_log_data` emission, so relayers can drop other
+/// programs' log data on a prefix comparison before decoding anything
+pub const EVENT_TAG: [u8; 4] = *b"LKEV";
+
+/// Leads the collateralization ratio emitted for monitors after every
+/// change to the totals, followed by the ratio in basis points as a
+/// little-endian u64. Not a cross-chain message, relayers skip it.
+pub const RATIO_TAG: [u8; 4] = *b"LKCR";
+
+/// Amounts cross the bridge as a big-endian uint256, as Solidity reads them
+pub const AMOUNT_LEN: usize = 32usize;
+/// The outbound nonce trails the message as a big-endian uint64, so the
+/// Ethereum side can reject an event it has already acted on
+pub const NONCE_LEN: usize = 8usize;
+pub const MESSAGE_LEN: usize = AMOUNT_LEN + DESTINATION_CHAIN_ADDRESS_LEN + NONCE_LEN;
+
+fn encode(amount: &U256, recipient: &[u8; DESTINATION_CHAIN_ADDRESS_LEN], nonce: u64, dst: &mut [u8; MESSAGE_LEN]) {
+    let (amount_dst, recipient_dst, nonce_dst) = mut_array_refs![dst, AMOUNT_LEN, DESTINATION_CHAIN_ADDRESS_LEN, NONCE_LEN];
+    amount.to_big_endian(&mut amount_dst[..]);
+    recipient_dst.copy_from_slice(&recipient[..]);
+    *nonce_dst = nonce.to_be_bytes();
+}
+
+fn decode(src: &[u8; MESSAGE_LEN]) -> (U256, [u8; DESTINATION_CHAIN_ADDRESS_LEN], u64) {
+    let (amount, recipient, nonce) = array_refs![src, AMOUNT_LEN, DESTINATION_CHAIN_ADDRESS_LEN, NONCE_LEN];
+    (U256::from_big_endian(&amount[..]), *recipient, u64::from_be_bytes(*nonce))
+}
+
+/// Encodes a lock of `amount` underlying tokens, to be minted to
+/// `recipient` on the destination chain.
+pub fn encode_lock(amount: &U256, recipient: &[u8; DESTINATION_CHAIN_ADDRESS_LEN], nonce: u64, dst: &mut [u8; MESSAGE_LEN]) {
+    encode(amount, recipient, nonce, dst)
+}
+
+pub fn decode_lock(src: &[u8; MESSAGE_LEN]) -> (U256, [u8; DESTINATION_CHAIN_ADDRESS_LEN], u64) {
+    decode(src)
+}
+
+/// Encodes a burn of `amount` underlying tokens, to be released to
+/// `recipient` on the destination chain.
+pub fn encode_burn(amount: &U256, recipient: &[u8; DESTINATION_CHAIN_ADDRESS_LEN], nonce: u64, dst: &mut [u8; MESSAGE_LEN]) {
+    encode(amount, recipient, nonce, dst)
+}
+
+pub fn decode_burn(src: &[u8; MESSAGE_LEN]) -> (U256, [u8; DESTINATION_CHAIN_ADDRESS_LEN], u64) {
+    decode(src)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{eth_address_to_destination, parse_eth_address};
+
+    // 1 ETH to 0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed, nonce 0x0102
+    const GOLDEN: [u8; MESSAGE_LEN] = [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x0d, 0xe0, 0xb6, 0xb3, 0xa7, 0x64, 0x00, 0x00,
+        0x5a, 0xae, 0xb6, 0x05, 0x3f, 0x3e, 0x94, 0xc9,
+        0xb9, 0xa0, 0x9f, 0x33, 0x66, 0x94, 0x35, 0xe7,
+        0xef, 0x1b, 0xea, 0xed, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x01, 0x02,
+    ];
+
+    #[test]
+    fn test_golden_vectors() {
+        let amount = U256::exp10(18);
+        let recipient = eth_address_to_destination(
+            parse_eth_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").unwrap()
+        );
+
+        let mut lock = [0u8; MESSAGE_LEN];
+        encode_lock(&amount, &recipient, 0x0102, &mut lock);
+        assert_eq!(lock, GOLDEN);
+        assert_eq!(decode_lock(&GOLDEN), (amount, recipient, 0x0102));
+
+        let mut burn = [0u8; MESSAGE_LEN];
+        encode_burn(&amount, &recipient, 0x0102, &mut burn);
+        assert_eq!(burn, GOLDEN);
+        assert_eq!(decode_burn(&GOLDEN), (amount, recipient, 0x0102));
+    }
+}